@@ -0,0 +1,434 @@
+use crate::import::{GltfRoot, ImportError};
+
+// accessor 가 가리키는 데이터를 CPU 에서 해석한다
+// 결과는 element 사이에 빈 공간이 없는 (tightly-packed) byte 배열이므로, 그대로 GPU buffer 로 올릴 수 있다
+// 파일이 잘못되어 buffer 범위를 벗어나는 경우 panic 대신 ImportError::BadAccessor 를 반환한다
+
+fn bad_accessor(acc: &gltf::Accessor, reason: impl Into<String>) -> ImportError {
+    ImportError::BadAccessor {
+        index: acc.index(),
+        reason: reason.into(),
+    }
+}
+
+fn invalid_dimensions(acc: &gltf::Accessor) -> ImportError {
+    bad_accessor(acc, format!("Invalid type {:?}", acc.dimensions()))
+}
+
+// buffer view 가 없는 accessor 는 파일 크기와 상관 없이 count 를 적을 수 있으므로 크기를 제한한다
+const MAX_ZERO_FILLED_SIZE: usize = 1 << 30;
+
+pub fn read_accessor(acc: &gltf::Accessor, root: &GltfRoot) -> Result<Vec<u8>, ImportError> {
+    let element_size = acc.size();
+    let count = acc.count();
+
+    let mut data = if let Some(view) = acc.view() {
+        // interleaved 된 buffer view 는 stride 가 element 크기보다 크다. element 만 골라서 이어 붙인다
+        let stride = view.stride().unwrap_or(element_size);
+        let buffer = &root.buffers[view.buffer().index()].0;
+        let start = view.offset() + acc.offset();
+
+        // 잘못된 count 로 큰 메모리를 할당하지 않도록, 할당하기 전에 마지막 element 가 buffer 안에 있는지 확인한다
+        let end = count.checked_sub(1).map_or(Some(start), |last| {
+            last.checked_mul(stride)
+                .and_then(|offset| offset.checked_add(start + element_size))
+        });
+        if end.map_or(true, |end| end > buffer.len()) {
+            return Err(bad_accessor(acc, "Accessor is out of buffer range"));
+        }
+
+        let mut data = Vec::with_capacity(element_size * count);
+        for i in 0..count {
+            let offset = start + i * stride;
+            let element = buffer
+                .get(offset..(offset + element_size))
+                .ok_or_else(|| bad_accessor(acc, "Element is out of buffer range"))?;
+            data.extend_from_slice(element);
+        }
+        data
+    } else {
+        // buffer view 가 없는 accessor 는 0 으로 채워져 있는 것으로 간주한다
+        match element_size.checked_mul(count) {
+            Some(size) if size <= MAX_ZERO_FILLED_SIZE => vec![0; size],
+            _ => return Err(bad_accessor(acc, format!("Count {} is too large", count))),
+        }
+    };
+
+    if let Some(sparse) = acc.sparse() {
+        apply_sparse(&mut data, element_size, &sparse, root)
+            .ok_or_else(|| bad_accessor(acc, "Sparse is out of range"))?;
+    }
+
+    Ok(data)
+}
+
+// sparse 로 지정된 element 들만 values 의 값으로 바꿔치기 한다
+// index 나 value 가 범위를 벗어나면 None
+fn apply_sparse(
+    data: &mut [u8],
+    element_size: usize,
+    sparse: &gltf::accessor::sparse::Sparse,
+    root: &GltfRoot,
+) -> Option<()> {
+    use gltf::accessor::sparse::IndexType;
+
+    let indices = sparse.indices();
+    let index_view = indices.view();
+    let index_buffer = &root.buffers[index_view.buffer().index()].0;
+    let index_start = index_view.offset() + indices.offset();
+
+    let values = sparse.values();
+    let value_view = values.view();
+    let value_buffer = &root.buffers[value_view.buffer().index()].0;
+    let value_start = value_view.offset() + values.offset();
+
+    for i in 0..sparse.count() {
+        let target = match indices.index_type() {
+            IndexType::U8 => *index_buffer.get(index_start + i)? as usize,
+            IndexType::U16 => {
+                let offset = index_start + i * 2;
+                let bytes = index_buffer.get(offset..(offset + 2))?;
+                u16::from_le_bytes([bytes[0], bytes[1]]) as usize
+            }
+            IndexType::U32 => {
+                let offset = index_start + i * 4;
+                let bytes = index_buffer.get(offset..(offset + 4))?;
+                u32::from_le_bytes(bytes.try_into().unwrap()) as usize
+            }
+        };
+
+        let source = value_start + i * element_size;
+        data.get_mut((target * element_size)..((target + 1) * element_size))?
+            .copy_from_slice(value_buffer.get(source..(source + element_size))?);
+    }
+    Some(())
+}
+
+// component type 에 상관 없이 f32 로 변환한다 (KHR_mesh_quantization 포함)
+// normalized 인 경우 glTF spec 의 규칙대로 [-1, 1] (signed) 또는 [0, 1] (unsigned) 범위로 바꾼다
+pub fn read_f32(acc: &gltf::Accessor, root: &GltfRoot) -> Result<Vec<f32>, ImportError> {
+    use gltf::accessor::DataType;
+
+    let data = read_accessor(acc, root)?;
+    let normalized = acc.normalized();
+    let convert = |value: f32, max: f32| {
+        if normalized {
+            (value / max).max(-1.0)
+        } else {
+            value
+        }
+    };
+
+    Ok(match acc.data_type() {
+        DataType::F32 => data
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect(),
+        DataType::I8 => data
+            .iter()
+            .map(|byte| convert(*byte as i8 as f32, i8::MAX as f32))
+            .collect(),
+        DataType::U8 => data
+            .iter()
+            .map(|byte| convert(*byte as f32, u8::MAX as f32))
+            .collect(),
+        DataType::I16 => data
+            .chunks_exact(2)
+            .map(|bytes| {
+                convert(
+                    i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+                    i16::MAX as f32,
+                )
+            })
+            .collect(),
+        DataType::U16 => data
+            .chunks_exact(2)
+            .map(|bytes| {
+                convert(
+                    u16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+                    u16::MAX as f32,
+                )
+            })
+            .collect(),
+        DataType::U32 => data
+            .chunks_exact(4)
+            .map(|bytes| {
+                convert(
+                    u32::from_le_bytes(bytes.try_into().unwrap()) as f32,
+                    u32::MAX as f32,
+                )
+            })
+            .collect(),
+    })
+}
+
+pub fn read_indices(acc: &gltf::Accessor, root: &GltfRoot) -> Result<Vec<u32>, ImportError> {
+    use gltf::accessor::DataType;
+
+    let data = read_accessor(acc, root)?;
+    Ok(match acc.data_type() {
+        DataType::U8 => data.iter().map(|i| *i as u32).collect(),
+        DataType::U16 => data
+            .chunks_exact(2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as u32)
+            .collect(),
+        DataType::U32 => data
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect(),
+        other => return Err(bad_accessor(acc, format!("Invalid index type {:?}", other))),
+    })
+}
+
+// JOINTS_0 은 unsigned byte 또는 unsigned short 의 vec4 이다
+pub fn read_joints(acc: &gltf::Accessor, root: &GltfRoot) -> Result<Vec<[u16; 4]>, ImportError> {
+    use gltf::accessor::DataType;
+
+    if acc.dimensions() != gltf::accessor::Dimensions::Vec4 {
+        return Err(invalid_dimensions(acc));
+    }
+    let data = read_accessor(acc, root)?;
+    let joints: Vec<u16> = match acc.data_type() {
+        DataType::U8 => data.iter().map(|i| *i as u16).collect(),
+        DataType::U16 => data
+            .chunks_exact(2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect(),
+        other => return Err(bad_accessor(acc, format!("Invalid joint type {:?}", other))),
+    };
+    Ok(joints
+        .chunks_exact(4)
+        .map(|j| [j[0], j[1], j[2], j[3]])
+        .collect())
+}
+
+pub fn read_vec2(acc: &gltf::Accessor, root: &GltfRoot) -> Result<Vec<[f32; 2]>, ImportError> {
+    if acc.dimensions() != gltf::accessor::Dimensions::Vec2 {
+        return Err(invalid_dimensions(acc));
+    }
+    Ok(read_f32(acc, root)?
+        .chunks_exact(2)
+        .map(|v| [v[0], v[1]])
+        .collect())
+}
+
+pub fn read_vec3(acc: &gltf::Accessor, root: &GltfRoot) -> Result<Vec<[f32; 3]>, ImportError> {
+    if acc.dimensions() != gltf::accessor::Dimensions::Vec3 {
+        return Err(invalid_dimensions(acc));
+    }
+    Ok(read_f32(acc, root)?
+        .chunks_exact(3)
+        .map(|v| [v[0], v[1], v[2]])
+        .collect())
+}
+
+pub fn read_vec4(acc: &gltf::Accessor, root: &GltfRoot) -> Result<Vec<[f32; 4]>, ImportError> {
+    if acc.dimensions() != gltf::accessor::Dimensions::Vec4 {
+        return Err(invalid_dimensions(acc));
+    }
+    Ok(read_f32(acc, root)?
+        .chunks_exact(4)
+        .map(|v| [v[0], v[1], v[2], v[3]])
+        .collect())
+}
+
+// COLOR_0 은 vec3 또는 vec4 이다. vec3 인 경우 alpha 는 1
+pub fn read_color(acc: &gltf::Accessor, root: &GltfRoot) -> Result<Vec<[f32; 4]>, ImportError> {
+    use gltf::accessor::Dimensions;
+
+    let values = read_f32(acc, root)?;
+    Ok(match acc.dimensions() {
+        Dimensions::Vec3 => values
+            .chunks_exact(3)
+            .map(|v| [v[0], v[1], v[2], 1.0])
+            .collect(),
+        Dimensions::Vec4 => values
+            .chunks_exact(4)
+            .map(|v| [v[0], v[1], v[2], v[3]])
+            .collect(),
+        _ => return Err(invalid_dimensions(acc)),
+    })
+}
+
+// morph target 의 변위 (displacement) 에 weight 를 곱해서 더한다
+// tangent 의 경우 displacement 는 xyz 만 있다 (w 는 그대로)
+pub fn add_weighted<const N: usize>(
+    base: &mut [[f32; N]],
+    displacements: &[[f32; 3]],
+    weight: f32,
+) {
+    for (value, displacement) in base.iter_mut().zip(displacements) {
+        for i in 0..3 {
+            value[i] += displacement[i] * weight;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    fn accessor(root: &GltfRoot, index: usize) -> gltf::Accessor<'_> {
+        root.document.accessors().nth(index).unwrap()
+    }
+
+    #[test]
+    fn huge_count_is_rejected_before_allocation() {
+        let bin = test_util::f32_bytes(&[0.0; 6]);
+        let root = test_util::load(
+            r#"{
+                "bufferViews": [{ "buffer": 0, "byteLength": 24 }],
+                "accessors": [
+                    { "bufferView": 0, "componentType": 5126, "count": 4000000000, "type": "VEC3" },
+                    { "componentType": 5126, "count": 4000000000, "type": "VEC3",
+                      "sparse": { "count": 1, "indices": { "bufferView": 0, "componentType": 5125 },
+                                  "values": { "bufferView": 0 } } },
+                    { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }
+                ]
+            }"#,
+            &bin,
+        );
+        for index in 0..3 {
+            assert!(matches!(
+                read_accessor(&accessor(&root, index), &root),
+                Err(ImportError::BadAccessor { .. })
+            ));
+        }
+    }
+
+    // position (vec3) 과 texcoord (vec2) 가 vertex 마다 번갈아 있다
+    #[test]
+    fn interleaved_view_is_deinterleaved() {
+        let bin = test_util::f32_bytes(&[
+            1.0, 2.0, 3.0, 0.1, 0.2, //
+            4.0, 5.0, 6.0, 0.3, 0.4, //
+            7.0, 8.0, 9.0, 0.5, 0.6,
+        ]);
+        let root = test_util::load(
+            r#"{
+                "bufferViews": [{ "buffer": 0, "byteLength": 60, "byteStride": 20 }],
+                "accessors": [
+                    { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" },
+                    { "bufferView": 0, "byteOffset": 12, "componentType": 5126, "count": 3, "type": "VEC2" }
+                ]
+            }"#,
+            &bin,
+        );
+        assert_eq!(
+            read_vec3(&accessor(&root, 0), &root).unwrap(),
+            vec![[1.0, 2.0, 3.0], [4.0, 5.0, 6.0], [7.0, 8.0, 9.0]]
+        );
+        assert_eq!(
+            read_vec2(&accessor(&root, 1), &root).unwrap(),
+            vec![[0.1, 0.2], [0.3, 0.4], [0.5, 0.6]]
+        );
+    }
+
+    #[test]
+    fn sparse_values_replace_base_or_zeros() {
+        let indices: Vec<u8> = [1u16, 3].iter().flat_map(|i| i.to_le_bytes()).collect();
+        let bin = [
+            test_util::f32_bytes(&[1.0, 2.0, 3.0, 4.0]),
+            indices,
+            test_util::f32_bytes(&[20.0, 40.0]),
+        ]
+        .concat();
+        let sparse = r#""sparse": {
+            "count": 2,
+            "indices": { "bufferView": 1, "componentType": 5123 },
+            "values": { "bufferView": 2 }
+        }"#;
+        let root = test_util::load(
+            &format!(
+                r#"{{
+                    "bufferViews": [
+                        {{ "buffer": 0, "byteLength": 16 }},
+                        {{ "buffer": 0, "byteOffset": 16, "byteLength": 4 }},
+                        {{ "buffer": 0, "byteOffset": 20, "byteLength": 8 }}
+                    ],
+                    "accessors": [
+                        {{ "bufferView": 0, "componentType": 5126, "count": 4, "type": "SCALAR", {} }},
+                        {{ "componentType": 5126, "count": 4, "type": "SCALAR", {} }}
+                    ]
+                }}"#,
+                sparse, sparse
+            ),
+            &bin,
+        );
+        assert_eq!(
+            read_f32(&accessor(&root, 0), &root).unwrap(),
+            vec![1.0, 20.0, 3.0, 40.0]
+        );
+        assert_eq!(
+            read_f32(&accessor(&root, 1), &root).unwrap(),
+            vec![0.0, 20.0, 0.0, 40.0]
+        );
+    }
+
+    #[test]
+    fn normalized_integers_are_mapped_to_unit_range() {
+        let bin = [
+            vec![0u8, 128, 255, 0],
+            [-128i8, -127, 0, 127].map(|v| v as u8).to_vec(),
+            [i16::MIN, i16::MAX]
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect(),
+            [0u16, u16::MAX]
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect(),
+        ]
+        .concat();
+        let root = test_util::load(
+            r#"{
+                "bufferViews": [{ "buffer": 0, "byteLength": 16 }],
+                "accessors": [
+                    { "bufferView": 0, "componentType": 5121, "normalized": true, "count": 4, "type": "SCALAR" },
+                    { "bufferView": 0, "byteOffset": 4, "componentType": 5120, "normalized": true, "count": 4, "type": "SCALAR" },
+                    { "bufferView": 0, "byteOffset": 8, "componentType": 5122, "normalized": true, "count": 2, "type": "SCALAR" },
+                    { "bufferView": 0, "byteOffset": 12, "componentType": 5123, "normalized": true, "count": 2, "type": "SCALAR" },
+                    { "bufferView": 0, "componentType": 5121, "count": 4, "type": "SCALAR" }
+                ]
+            }"#,
+            &bin,
+        );
+        let read = |index| read_f32(&accessor(&root, index), &root).unwrap();
+        assert_eq!(read(0), vec![0.0, 128.0 / 255.0, 1.0, 0.0]);
+        // signed 는 최솟값이 -1 보다 작아지지 않도록 clamp 한다
+        assert_eq!(read(1), vec![-1.0, -1.0, 0.0, 1.0]);
+        assert_eq!(read(2), vec![-1.0, 1.0]);
+        assert_eq!(read(3), vec![0.0, 1.0]);
+        // normalized 가 아니면 정수 값 그대로
+        assert_eq!(read(4), vec![0.0, 128.0, 255.0, 0.0]);
+    }
+
+    #[test]
+    fn byte_joints_are_widened() {
+        let root = test_util::load(
+            r#"{
+                "bufferViews": [{ "buffer": 0, "byteLength": 8 }],
+                "accessors": [
+                    { "bufferView": 0, "componentType": 5121, "count": 2, "type": "VEC4" },
+                    { "bufferView": 0, "componentType": 5123, "count": 1, "type": "VEC4" },
+                    { "bufferView": 0, "componentType": 5121, "count": 2, "type": "VEC4", "normalized": true }
+                ]
+            }"#,
+            &[0, 1, 2, 255, 3, 0, 0, 0],
+        );
+        assert_eq!(
+            read_joints(&accessor(&root, 0), &root).unwrap(),
+            vec![[0, 1, 2, 255], [3, 0, 0, 0]]
+        );
+        assert_eq!(
+            read_joints(&accessor(&root, 1), &root).unwrap(),
+            vec![[256, 65282, 3, 0]]
+        );
+        // WEIGHTS_0 은 normalized 정수일 수 있다
+        assert_eq!(
+            read_vec4(&accessor(&root, 2), &root).unwrap()[0],
+            [0.0, 1.0 / 255.0, 2.0 / 255.0, 1.0]
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use cgmath::*;
+use std::collections::HashMap;
+
+// import 시점에 CPU 에서 하는 vertex data 가공
+
+// index 를 풀어서 vertex 를 index 순서대로 나열한다
+pub fn unindex<T: Copy>(data: &[T], indices: &[u32]) -> Vec<T> {
+    indices.iter().map(|i| data[*i as usize]).collect()
+}
+
+// 아래 함수들은 index 가 없는 triangle list 를 받는다 (vertex 3개가 하나의 면)
+
+pub fn flat_normals(positions: &[[f32; 3]]) -> Vec<[f32; 3]> {
+    positions
+        .chunks_exact(3)
+        .flat_map(|triangle| {
+            let normal = normalize_or_default(face_normal(triangle)).into();
+            [normal; 3]
+        })
+        .collect()
+}
+
+// 같은 위치를 공유하는 면들 중, 이 면과의 각도가 threshold 이하인 면들의 normal 을 (넓이 가중치로) 평균낸다
+pub fn smooth_normals(positions: &[[f32; 3]], angle_threshold: Rad<f32>) -> Vec<[f32; 3]> {
+    let face_normals: Vec<Vector3<f32>> = positions.chunks_exact(3).map(face_normal).collect();
+    let unit_face_normals: Vec<Vector3<f32>> = face_normals
+        .iter()
+        .map(|n| normalize_or_default(*n))
+        .collect();
+
+    let mut corners_by_position: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+    for (corner, position) in positions.iter().enumerate() {
+        corners_by_position
+            .entry(position.map(f32::to_bits))
+            .or_default()
+            .push(corner);
+    }
+
+    let cos_threshold = angle_threshold.cos();
+    positions
+        .iter()
+        .enumerate()
+        .map(|(corner, position)| {
+            let face = corner / 3;
+            let mut sum = Vector3::zero();
+            for other in &corners_by_position[&position.map(f32::to_bits)] {
+                let other_face = other / 3;
+                if unit_face_normals[face].dot(unit_face_normals[other_face]) >= cos_threshold {
+                    sum += face_normals[other_face];
+                }
+            }
+            if sum.magnitude2() > 0.0 {
+                sum.normalize().into()
+            } else {
+                unit_face_normals[face].into()
+            }
+        })
+        .collect()
+}
+
+// Blender 등 대부분의 도구가 쓰는 MikkTSpace 로 tangent 를 만든다. w 는 bitangent 의 부호
+pub fn mikktspace_tangents(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    tex_coords: &[[f32; 2]],
+) -> Vec<[f32; 4]> {
+    let mut geometry = MikkTSpaceGeometry {
+        positions,
+        normals,
+        tex_coords,
+        tangents: vec![[1.0, 0.0, 0.0, 1.0]; positions.len()],
+    };
+    if !mikktspace::generate_tangents(&mut geometry) {
+        log::warn!("Failed to generate tangents");
+    }
+    geometry.tangents
+}
+
+struct MikkTSpaceGeometry<'a> {
+    positions: &'a [[f32; 3]],
+    normals: &'a [[f32; 3]],
+    tex_coords: &'a [[f32; 2]],
+    tangents: Vec<[f32; 4]>,
+}
+
+impl mikktspace::Geometry for MikkTSpaceGeometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.positions.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.positions[face * 3 + vert]
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.normals[face * 3 + vert]
+    }
+
+    // glTF 의 UV 는 원점이 왼쪽 위이다. MikkTSpace (와 Blender) 는 왼쪽 아래를 원점으로 보므로 v 를 뒤집어서 넘겨야
+    // w 의 부호가 glTF 의 bitangent 방향과 맞는다
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        let [u, v] = self.tex_coords[face * 3 + vert];
+        [u, 1.0 - v]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[face * 3 + vert] = tangent;
+    }
+}
+
+// 크기는 면의 넓이의 2배
+fn face_normal(triangle: &[[f32; 3]]) -> Vector3<f32> {
+    let p0 = Vector3::from(triangle[0]);
+    let p1 = Vector3::from(triangle[1]);
+    let p2 = Vector3::from(triangle[2]);
+    (p1 - p0).cross(p2 - p0)
+}
+
+// 넓이가 0 인 면은 방향을 알 수 없으므로 아무 방향이나 쓴다
+fn normalize_or_default(v: Vector3<f32>) -> Vector3<f32> {
+    if v.magnitude2() > 0.0 {
+        v.normalize()
+    } else {
+        Vector3::unit_z()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // +Z 를 보는 사각형. u 는 +x 방향, v 는 (glTF 규칙대로) -y 방향으로 증가한다
+    #[test]
+    fn mikktspace_tangents_use_gltf_handedness() {
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        let normals = [[0.0, 0.0, 1.0]; 6];
+        let tex_coords = [
+            [0.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 0.0],
+            [0.0, 1.0],
+            [1.0, 0.0],
+            [0.0, 0.0],
+        ];
+        for tangent in mikktspace_tangents(&positions, &normals, &tex_coords) {
+            assert!((tangent[0] - 1.0).abs() < 1e-5, "{:?}", tangent);
+            assert!(
+                tangent[1].abs() < 1e-5 && tangent[2].abs() < 1e-5,
+                "{:?}",
+                tangent
+            );
+            assert_eq!(tangent[3], 1.0);
+        }
+    }
+}
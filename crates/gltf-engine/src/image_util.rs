@@ -1,6 +1,80 @@
+use image::{ImageBuffer, Luma, LumaA, Rgb, Rgba};
+
 pub fn white_image() -> image::DynamicImage {
     let buffer = image::ImageBuffer::from_fn(1, 1, |_x, _y| {
         image::Luma([255u8])
     });
     buffer.into()
 }
+
+// gltf::image::Data 는 포맷별로 픽셀이 raw bytes 로 들어있으므로, 다시 image crate 의 타입으로 감싸서 RGBA8 로 변환한다
+pub fn gltf_image_to_rgba8(data: &gltf::image::Data) -> anyhow::Result<image::RgbaImage> {
+    use gltf::image::Format;
+
+    let (width, height) = (data.width, data.height);
+    let u16_pixels = || -> Vec<u16> {
+        data.pixels
+            .chunks_exact(2)
+            .map(|c| u16::from_ne_bytes([c[0], c[1]]))
+            .collect()
+    };
+    let f32_pixels = || -> Vec<f32> {
+        data.pixels
+            .chunks_exact(4)
+            .map(|c| f32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+            .collect()
+    };
+
+    let image: Option<image::DynamicImage> = match data.format {
+        Format::R8 => ImageBuffer::<Luma<u8>, _>::from_raw(width, height, data.pixels.clone())
+            .map(Into::into),
+        Format::R8G8 => ImageBuffer::<LumaA<u8>, _>::from_raw(width, height, data.pixels.clone())
+            .map(Into::into),
+        Format::R8G8B8 => ImageBuffer::<Rgb<u8>, _>::from_raw(width, height, data.pixels.clone())
+            .map(Into::into),
+        Format::R8G8B8A8 => {
+            ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, data.pixels.clone())
+                .map(Into::into)
+        }
+        Format::R16 => ImageBuffer::<Luma<u16>, _>::from_raw(width, height, u16_pixels())
+            .map(Into::into),
+        Format::R16G16 => ImageBuffer::<LumaA<u16>, _>::from_raw(width, height, u16_pixels())
+            .map(Into::into),
+        Format::R16G16B16 => ImageBuffer::<Rgb<u16>, _>::from_raw(width, height, u16_pixels())
+            .map(Into::into),
+        Format::R16G16B16A16 => {
+            ImageBuffer::<Rgba<u16>, _>::from_raw(width, height, u16_pixels()).map(Into::into)
+        }
+        Format::R32G32B32FLOAT => {
+            ImageBuffer::<Rgb<f32>, _>::from_raw(width, height, f32_pixels()).map(Into::into)
+        }
+        Format::R32G32B32A32FLOAT => {
+            ImageBuffer::<Rgba<f32>, _>::from_raw(width, height, f32_pixels()).map(Into::into)
+        }
+    };
+
+    let image = image.ok_or_else(|| {
+        anyhow::anyhow!("Image data does not match its size {}x{}", width, height)
+    })?;
+    Ok(image.to_rgba8())
+}
+
+// sampler 의 mipmap filter 가 의미를 가지려면 mip level 이 있어야 함
+pub fn generate_mips(base: image::RgbaImage) -> Vec<image::RgbaImage> {
+    let mut mips = vec![base];
+    loop {
+        let last = mips.last().unwrap();
+        let (width, height) = last.dimensions();
+        if width <= 1 && height <= 1 {
+            break;
+        }
+        let next = image::imageops::resize(
+            last,
+            (width / 2).max(1),
+            (height / 2).max(1),
+            image::imageops::FilterType::Triangle,
+        );
+        mips.push(next);
+    }
+    mips
+}
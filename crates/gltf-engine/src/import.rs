@@ -1,11 +1,13 @@
+use crate::accessor;
+use crate::geometry;
+use crate::image_util;
+use crate::loader::{LoadProgress, LoadStage};
 use crate::mesh::*;
 use crate::model::*;
-use crate::texture;
 use crate::*;
-use crate::{MaterialUniform, NodeUniform};
 use std::collections::HashMap;
+use std::fmt;
 use uuid::Uuid;
-use wgpu::util::DeviceExt;
 
 pub struct GltfRoot {
     pub document: gltf::Document,
@@ -13,43 +15,285 @@ pub struct GltfRoot {
     pub images: Vec<gltf::image::Data>,
 }
 
-pub struct WgpuDeps<'a> {
-    pub device: &'a wgpu::Device,
-    pub queue: &'a wgpu::Queue,
-    pub node_uniform_layout: &'a wgpu::BindGroupLayout,
-    pub material_uniform_layout: &'a wgpu::BindGroupLayout,
-    pub white_texture: &'a texture::Texture,
+pub fn load_gltf(path: impl AsRef<std::path::Path>) -> Result<GltfRoot, ImportError> {
+    load_gltf_with_progress(path.as_ref(), &mut |_| Ok(()))
 }
 
-pub fn import_gltf(root: &GltfRoot, deps: &WgpuDeps) -> ImportedGltf {
+// GLB 또는 glTF (JSON) 의 내용. 경로가 없으므로 바깥 파일을 참조할 수 없다
+// NOTE: gltf crate 는 slice 에서 image 를 uri 로 (data URI 포함) 읽지 못한다. image 는 buffer view 에 있어야 한다
+pub fn load_gltf_slice(bytes: &[u8]) -> Result<GltfRoot, ImportError> {
+    load_gltf_slice_with_progress(bytes, &mut |_| Ok(()))
+}
+
+pub(crate) fn load_gltf_with_progress(
+    path: &std::path::Path,
+    progress: &mut dyn FnMut(LoadProgress) -> Result<(), ImportError>,
+) -> Result<GltfRoot, ImportError> {
+    // gltf::import 와 같이 uri 는 glTF 파일이 있는 directory 를 기준으로 한다
+    let base = path.parent().unwrap_or_else(|| std::path::Path::new("./"));
+    read_gltf(gltf::Gltf::open(path)?, Some(base), progress)
+}
+
+pub(crate) fn load_gltf_slice_with_progress(
+    bytes: &[u8],
+    progress: &mut dyn FnMut(LoadProgress) -> Result<(), ImportError>,
+) -> Result<GltfRoot, ImportError> {
+    read_gltf(gltf::Gltf::from_slice(bytes)?, None, progress)
+}
+
+// gltf::import 는 모든 buffer 와 image 를 한 번에 읽으므로, 취소할 수 있도록 단계를 나눠서 읽는다
+fn read_gltf(
+    gltf: gltf::Gltf,
+    base: Option<&std::path::Path>,
+    progress: &mut dyn FnMut(LoadProgress) -> Result<(), ImportError>,
+) -> Result<GltfRoot, ImportError> {
+    let gltf::Gltf { document, blob } = gltf;
+
+    progress(LoadProgress {
+        stage: LoadStage::LoadingBuffers,
+        completed: 0,
+        total: 1,
+    })?;
+    let buffers = gltf::import_buffers(&document, base, blob)?;
+
+    let image_count = document.images().len();
+    let mut images = Vec::with_capacity(image_count);
+    for image in document.images() {
+        progress(LoadProgress {
+            stage: LoadStage::DecodingImages,
+            completed: images.len(),
+            total: image_count,
+        })?;
+        images.push(gltf::image::Data::from_source(
+            image.source(),
+            base,
+            &buffers,
+        )?);
+    }
+
+    Ok(GltfRoot {
+        document,
+        buffers,
+        images,
+    })
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(std::io::Error),
+    // JSON, GLB, base64, image decoding 등 파일 자체를 해석하지 못한 경우
+    Parse(gltf::Error),
+    // 파일은 올바르지만 아직 지원하지 않는 기능 (extension, image format 등)
+    Unsupported(String),
+    // accessor 가 buffer 범위를 벗어나거나, attribute 에 맞지 않는 type 인 경우
+    BadAccessor { index: usize, reason: String },
+    // 불러오는 도중에 사용자가 취소한 경우
+    Cancelled,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(e) => write!(f, "I/O error: {}", e),
+            ImportError::Parse(e) => write!(f, "Failed to parse glTF: {}", e),
+            ImportError::Unsupported(feature) => write!(f, "Unsupported: {}", feature),
+            ImportError::BadAccessor { index, reason } => {
+                write!(f, "Bad accessor {}: {}", index, reason)
+            }
+            ImportError::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImportError::Io(e) => Some(e),
+            ImportError::Parse(e) => Some(e),
+            ImportError::Unsupported(_)
+            | ImportError::BadAccessor { .. }
+            | ImportError::Cancelled => None,
+        }
+    }
+}
+
+impl From<gltf::Error> for ImportError {
+    fn from(e: gltf::Error) -> Self {
+        match e {
+            gltf::Error::Io(e) => ImportError::Io(e),
+            gltf::Error::UnsupportedScheme => ImportError::Unsupported("URI scheme".into()),
+            gltf::Error::UnsupportedImageEncoding => {
+                ImportError::Unsupported("Image encoding".into())
+            }
+            gltf::Error::ExternalReferenceInSliceImport => {
+                ImportError::Unsupported("External reference in slice import".into())
+            }
+            e => ImportError::Parse(e),
+        }
+    }
+}
+
+// extensionsRequired 에 이 목록 외의 extension 이 있으면 제대로 그릴 수 없으므로 불러오지 않는다
+const SUPPORTED_EXTENSIONS: &[&str] = &["KHR_mesh_quantization"];
+
+#[derive(Debug, Copy, Clone)]
+pub enum NormalGeneration {
+    Flat,
+    // 인접한 면과의 각도가 threshold 이하이면 부드럽게 이어지도록 normal 을 평균낸다
+    Smooth { angle_threshold: cgmath::Deg<f32> },
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ImportOptions {
+    // NORMAL attribute 가 없는 triangle 에 적용된다
+    pub normal_generation: NormalGeneration,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            normal_generation: NormalGeneration::Flat,
+        }
+    }
+}
+
+// 불러오기는 두 단계로 나뉜다
+// 1. prepare_gltf: glTF 를 CPU model (ImportedGltf) 로 만든다 (image 변환, mipmap, accessor 해석, normal / tangent 생성 등). worker thread 에서 실행할 수 있다
+// 2. Engine::load_prepared: ResourceCache 가 model 에 맞춰 GPU 자원을 만든다. render thread 에서 실행해야 한다
+
+pub struct PreparedGltf {
+    document: gltf::Document,
+    pub(crate) model: ImportedGltf,
+}
+
+impl PreparedGltf {
+    // GPU 없이 import 결과를 확인할 때 쓴다
+    pub fn model(&self) -> &ImportedGltf {
+        &self.model
+    }
+
+    // glTF 파일 자신과 uri 로 참조하는 buffer, image 파일들. data URI 와 GLB 내부 데이터는 제외한다
+    pub fn source_files(&self, path: &std::path::Path) -> Vec<std::path::PathBuf> {
+        let base = path.parent().unwrap_or_else(|| std::path::Path::new(""));
+        let buffer_uris = self
+            .document
+            .buffers()
+            .filter_map(|buffer| match buffer.source() {
+                gltf::buffer::Source::Uri(uri) => Some(uri),
+                gltf::buffer::Source::Bin => None,
+            });
+        let image_uris = self
+            .document
+            .images()
+            .filter_map(|image| match image.source() {
+                gltf::image::Source::Uri { uri, .. } => Some(uri),
+                gltf::image::Source::View { .. } => None,
+            });
+
+        let mut files = vec![path.to_path_buf()];
+        for uri in buffer_uris.chain(image_uris) {
+            if uri.starts_with("data:") {
+                continue;
+            }
+            // uri 는 percent-encoding 되어 있을 수 있다
+            let decoded = urlencoding::decode(uri).map_or(uri.into(), |d| d.into_owned());
+            let file = base.join(decoded);
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+        files
+    }
+}
+
+// 단계가 바뀔 때마다 progress 를 호출한다. progress 가 Err 를 반환하면 (취소) 그대로 중단한다
+pub fn prepare_gltf(
+    root: GltfRoot,
+    options: &ImportOptions,
+    progress: &mut dyn FnMut(LoadProgress) -> Result<(), ImportError>,
+) -> Result<PreparedGltf, ImportError> {
+    if let Some(extension) = root
+        .document
+        .extensions_required()
+        .find(|e| !SUPPORTED_EXTENSIONS.contains(e))
+    {
+        return Err(ImportError::Unsupported(format!("Extension {}", extension)));
+    }
+
     let document = &root.document;
 
+    let image_count = root.images.len();
+    let mut images = HashMap::new();
+    let mut image_ids = HashMap::new();
+    for (index, data) in root.images.iter().enumerate() {
+        progress(LoadProgress {
+            stage: LoadStage::ProcessingImages,
+            completed: index,
+            total: image_count,
+        })?;
+        let rgba = image_util::gltf_image_to_rgba8(data)
+            .map_err(|e| ImportError::Unsupported(format!("Image {}: {}", index, e)))?;
+        let image = Image {
+            id: Uuid::new_v4(),
+            mips: image_util::generate_mips(rgba),
+            source_info: ImageSourceInfo::Gltf { index },
+        };
+        image_ids.insert(index, image.id);
+        images.insert(image.id, image);
+    }
+
+    let samplers: HashMap<Uuid, Sampler> = document
+        .samplers()
+        .map(import_sampler)
+        .chain(std::iter::once(import_default_sampler()))
+        .map(|s| (s.id, s))
+        .collect();
+
+    // glTF texture 가 sampler 를 지정하지 않은 경우 (None) default sampler 를 사용한다
+    let sampler_ids: HashMap<Option<usize>, Uuid> = samplers
+        .values()
+        .map(|s| match s.source_info {
+            SamplerSourceInfo::Gltf { index } => (Some(index), s.id),
+            SamplerSourceInfo::SomethingElse => (None, s.id),
+        })
+        .collect();
+
+    let texture_importer = TextureImporter {
+        image_ids,
+        sampler_ids,
+    };
+
     let materials: HashMap<Uuid, Material> = document
         .materials()
-        .map(|m| import_material(m, deps))
+        .map(|m| import_material(m, &texture_importer))
         .map(|m| (m.id, m))
         .collect();
 
     let material_ids: HashMap<usize, Uuid> =
         materials.values().map(|m| (m.gltf_index(), m.id)).collect();
 
-    let meshes: HashMap<Uuid, Mesh> = document
-        .meshes()
-        .map(|mesh| import_mesh(mesh, root, deps, &material_ids))
-        .map(|mesh| (mesh.id, mesh))
-        .collect();
+    let mesh_count = document.meshes().len();
+    let mut meshes = HashMap::new();
+    for mesh in document.meshes() {
+        progress(LoadProgress {
+            stage: LoadStage::ProcessingMeshes,
+            completed: meshes.len(),
+            total: mesh_count,
+        })?;
+        let mesh = import_mesh(mesh, &root, options, &material_ids)?;
+        meshes.insert(mesh.id, mesh);
+    }
 
     let mesh_ids: HashMap<usize, Uuid> = meshes.values().map(|m| (m.gltf_index(), m.id)).collect();
 
-    let node_ids: HashMap<usize, Uuid> = root
-        .document
+    let node_ids: HashMap<usize, Uuid> = document
         .nodes()
         .map(|n| (n.index(), Uuid::new_v4()))
         .collect();
 
     let nodes: HashMap<Uuid, Node> = document
         .nodes()
-        .map(|n| import_node(n, deps, &mesh_ids, &node_ids))
+        .map(|n| import_node(n, &mesh_ids, &node_ids))
         .map(|n| (n.id, n))
         .collect();
 
@@ -64,15 +308,111 @@ pub fn import_gltf(root: &GltfRoot, deps: &WgpuDeps) -> ImportedGltf {
     let default_scene_id = document
         .default_scene()
         .map(|scene| scene_ids[&scene.index()]);
+    // default scene 이 없으면 첫 번째 scene 을 보여준다
+    let active_scene_id = default_scene_id.or_else(|| {
+        document
+            .scenes()
+            .next()
+            .map(|scene| scene_ids[&scene.index()])
+    });
 
-    // TODO: texture, sampler
-
-    ImportedGltf {
+    let model = ImportedGltf {
         default_scene_id,
+        active_scene_id,
         scenes,
         nodes,
         meshes,
         materials,
+        images,
+        samplers,
+    };
+
+    Ok(PreparedGltf {
+        document: root.document,
+        model,
+    })
+}
+
+fn import_sampler(sampler: gltf::texture::Sampler) -> Sampler {
+    use gltf::texture::{MagFilter, MinFilter};
+
+    let address_mode = |mode: gltf::texture::WrappingMode| match mode {
+        gltf::texture::WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        gltf::texture::WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+        gltf::texture::WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+    };
+
+    let mag_filter = match sampler.mag_filter() {
+        Some(MagFilter::Nearest) => wgpu::FilterMode::Nearest,
+        Some(MagFilter::Linear) | None => wgpu::FilterMode::Linear,
+    };
+
+    // (min_filter, mipmap_filter)
+    let (min_filter, mipmap_filter) = match sampler.min_filter() {
+        Some(MinFilter::Nearest) => (wgpu::FilterMode::Nearest, None),
+        Some(MinFilter::Linear) => (wgpu::FilterMode::Linear, None),
+        Some(MinFilter::NearestMipmapNearest) => {
+            (wgpu::FilterMode::Nearest, Some(wgpu::FilterMode::Nearest))
+        }
+        Some(MinFilter::LinearMipmapNearest) => {
+            (wgpu::FilterMode::Linear, Some(wgpu::FilterMode::Nearest))
+        }
+        Some(MinFilter::NearestMipmapLinear) => {
+            (wgpu::FilterMode::Nearest, Some(wgpu::FilterMode::Linear))
+        }
+        Some(MinFilter::LinearMipmapLinear) | None => {
+            (wgpu::FilterMode::Linear, Some(wgpu::FilterMode::Linear))
+        }
+    };
+
+    Sampler {
+        id: Uuid::new_v4(),
+        mag_filter,
+        min_filter,
+        mipmap_filter,
+        address_mode_u: address_mode(sampler.wrap_s()),
+        address_mode_v: address_mode(sampler.wrap_t()),
+        source_info: SamplerSourceInfo::Gltf {
+            index: sampler.index().unwrap(),
+        },
+    }
+}
+
+fn import_default_sampler() -> Sampler {
+    // glTF spec 에서 sampler 가 없으면 repeat wrapping + auto filtering 을 쓰라고 되어 있음
+    Sampler {
+        id: Uuid::new_v4(),
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: Some(wgpu::FilterMode::Linear),
+        address_mode_u: wgpu::AddressMode::Repeat,
+        address_mode_v: wgpu::AddressMode::Repeat,
+        source_info: SamplerSourceInfo::SomethingElse,
+    }
+}
+
+struct TextureImporter {
+    image_ids: HashMap<usize, Uuid>,
+    sampler_ids: HashMap<Option<usize>, Uuid>,
+}
+
+impl TextureImporter {
+    fn texture_ref(&self, texture: gltf::texture::Texture, tex_coord: u32) -> TextureRef {
+        // renderer 는 TEXCOORD_0, TEXCOORD_1 만 가지고 있으므로 model 에도 실제로 쓰는 값을 넣는다
+        let tex_coord = if tex_coord > 1 {
+            log::warn!(
+                "TEXCOORD_{} is not supported. TEXCOORD_0 will be used",
+                tex_coord
+            );
+            0
+        } else {
+            tex_coord
+        };
+        TextureRef {
+            image_id: self.image_ids[&texture.source().index()],
+            sampler_id: self.sampler_ids[&texture.sampler().index()],
+            tex_coord,
+        }
     }
 }
 
@@ -83,6 +423,8 @@ fn import_scene(scene: gltf::Scene, node_ids: &HashMap<usize, Uuid>) -> Scene {
     }
     Scene {
         id: Uuid::new_v4(),
+        name: scene.name().map(|name| name.to_owned()),
+        extras: import_extras(scene.extras()),
         nodes,
         source_info: SceneSourceInfo::Gltf {
             index: scene.index(),
@@ -90,40 +432,28 @@ fn import_scene(scene: gltf::Scene, node_ids: &HashMap<usize, Uuid>) -> Scene {
     }
 }
 
+// extras 는 형식이 정해져 있지 않으므로 JSON 원문 그대로 보관한다
+fn import_extras(extras: &gltf::json::Extras) -> Option<String> {
+    extras.as_ref().map(|raw| raw.get().to_owned())
+}
+
 fn import_node(
     node: gltf::Node,
-    deps: &WgpuDeps,
     mesh_ids: &HashMap<usize, Uuid>,
     node_ids: &HashMap<usize, Uuid>,
 ) -> Node {
     let transform = import_transform(node.transform());
 
-    let uniform_buffer = deps.device.create_buffer(&wgpu::BufferDescriptor {
-        label: Some("Uniform Buffer"),
-        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        size: std::mem::size_of::<NodeUniform>() as wgpu::BufferAddress,
-        mapped_at_creation: false,
-    });
-
-    let uniform_bind_group = deps.device.create_bind_group(&wgpu::BindGroupDescriptor {
-        layout: &deps.node_uniform_layout,
-        entries: &[wgpu::BindGroupEntry {
-            binding: 0,
-            resource: uniform_buffer.as_entire_binding(),
-        }],
-        label: Some("primitive_transform_bind_group"),
-    });
-
     Node {
         id: node_ids[&node.index()],
+        name: node.name().map(|name| name.to_owned()),
+        extras: import_extras(node.extras()),
         transform,
         children: node
             .children()
             .map(|child| node_ids[&child.index()])
             .collect(),
         mesh_id: node.mesh().map(|m| mesh_ids[&m.index()]),
-        uniform_buffer,
-        uniform_bind_group,
         source_info: NodeSourceInfo::Gltf {
             index: node.index(),
         },
@@ -172,52 +502,40 @@ fn import_transform(transform: gltf::scene::Transform) -> NodeTransform {
     }
 }
 
-fn import_material(material: gltf::Material, deps: &WgpuDeps) -> Material {
-    if material.double_sided() {
-        log::warn!("Double sided material found");
-    }
+fn import_material(material: gltf::Material, texture_importer: &TextureImporter) -> Material {
     let emissive_factor: cgmath::Vector3<f32> = material.emissive_factor().into();
     let mr = material.pbr_metallic_roughness();
     let base_color_factor: cgmath::Vector4<f32> = mr.base_color_factor().into();
-    let material_uniform = MaterialUniform {
-        base_color_factor: base_color_factor.into(),
-        emissive_factor: emissive_factor.into(),
-        _pad: 0.0,
+    let metallic_factor = mr.metallic_factor();
+    let roughness_factor = mr.roughness_factor();
+
+    let base_color_texture = mr
+        .base_color_texture()
+        .map(|info| texture_importer.texture_ref(info.texture(), info.tex_coord()));
+    let metallic_roughness_texture = mr
+        .metallic_roughness_texture()
+        .map(|info| texture_importer.texture_ref(info.texture(), info.tex_coord()));
+    let normal_texture = material.normal_texture();
+    let normal_scale = normal_texture.as_ref().map(|t| t.scale()).unwrap_or(1.0);
+    let normal_texture =
+        normal_texture.map(|t| texture_importer.texture_ref(t.texture(), t.tex_coord()));
+    let occlusion_texture = material.occlusion_texture();
+    let occlusion_strength = occlusion_texture
+        .as_ref()
+        .map(|t| t.strength())
+        .unwrap_or(1.0);
+    let occlusion_texture =
+        occlusion_texture.map(|t| texture_importer.texture_ref(t.texture(), t.tex_coord()));
+    let emissive_texture = material
+        .emissive_texture()
+        .map(|info| texture_importer.texture_ref(info.texture(), info.tex_coord()));
+
+    let alpha_mode = match material.alpha_mode() {
+        gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+        gltf::material::AlphaMode::Mask => AlphaMode::Mask,
+        gltf::material::AlphaMode::Blend => AlphaMode::Blend,
     };
-
-    let uniform_buffer = deps.device.create_buffer(&wgpu::BufferDescriptor {
-        label: Some("Material Uniform Buffer"),
-        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        size: std::mem::size_of::<MaterialUniform>() as wgpu::BufferAddress,
-        mapped_at_creation: false,
-    });
-
-    let material_bind_group = deps.device.create_bind_group(&wgpu::BindGroupDescriptor {
-        layout: &deps.material_uniform_layout,
-        entries: &[
-            wgpu::BindGroupEntry {
-                binding: 0,
-                resource: uniform_buffer.as_entire_binding(),
-            },
-            wgpu::BindGroupEntry {
-                binding: 1,
-                // TODO: imported texture
-                resource: wgpu::BindingResource::TextureView(&deps.white_texture.view),
-            },
-            wgpu::BindGroupEntry {
-                binding: 2,
-                // TODO: imported sampler
-                resource: wgpu::BindingResource::Sampler(&deps.white_texture.sampler),
-            },
-        ],
-        label: Some("material_bind_group"),
-    });
-
-    deps.queue.write_buffer(
-        &uniform_buffer,
-        0,
-        bytemuck::cast_slice(&[material_uniform]),
-    );
+    let alpha_cutoff = material.alpha_cutoff().unwrap_or(0.5);
 
     let source_info = MaterialSourceInfo::Gltf {
         index: material.index().unwrap(),
@@ -225,10 +543,22 @@ fn import_material(material: gltf::Material, deps: &WgpuDeps) -> Material {
 
     Material {
         id: Uuid::new_v4(),
+        name: material.name().map(|name| name.to_owned()),
+        extras: import_extras(material.extras()),
         base_color_factor,
         emissive_factor,
-        material_bind_group,
-        uniform_buffer,
+        metallic_factor,
+        roughness_factor,
+        normal_scale,
+        occlusion_strength,
+        base_color_texture,
+        metallic_roughness_texture,
+        normal_texture,
+        occlusion_texture,
+        emissive_texture,
+        alpha_mode,
+        alpha_cutoff,
+        double_sided: material.double_sided(),
         source_info,
     }
 }
@@ -236,158 +566,441 @@ fn import_material(material: gltf::Material, deps: &WgpuDeps) -> Material {
 fn import_mesh(
     mesh: gltf::Mesh,
     root: &GltfRoot,
-    deps: &WgpuDeps,
+    options: &ImportOptions,
     material_ids: &HashMap<usize, Uuid>,
-) -> Mesh {
-    Mesh {
+) -> Result<Mesh, ImportError> {
+    let weights = mesh.weights().unwrap_or(&[]);
+    Ok(Mesh {
         id: Uuid::new_v4(),
+        name: mesh.name().map(|name| name.to_owned()),
+        extras: import_extras(mesh.extras()),
         primitives: mesh
             .primitives()
-            .map(|p| import_primitive(p, root, deps, material_ids))
-            .collect(),
+            .map(|p| import_primitive(p, weights, root, options, material_ids))
+            .collect::<Result<_, _>>()?,
         source_info: MeshSourceInfo::Gltf {
             index: mesh.index(),
         },
-    }
+    })
 }
 
 fn import_primitive(
     primitive: gltf::Primitive,
+    weights: &[f32],
     root: &GltfRoot,
-    deps: &WgpuDeps,
+    options: &ImportOptions,
     material_ids: &HashMap<usize, Uuid>,
-) -> Option<MeshPrimitive> {
+) -> Result<Option<MeshPrimitive>, ImportError> {
     use gltf::mesh::*;
 
     let index = primitive.index();
 
-    let mode = primitive.mode();
-    if mode != Mode::Triangles {
-        eprintln!("Primitive {} is not of triangles mode. Skip", index);
-        return None;
-    }
-    let index_acc = primitive.indices().expect("Failed to get index accessor");
-    let (index_buffer, index_size) = import_buffer(
-        &index_acc,
-        root,
-        deps,
-        None,
-        "Vertex Index",
-        wgpu::BufferUsages::INDEX,
-    )
-    .expect("Failed to get index buffer");
-    let index_format = match index_size {
-        2 => wgpu::IndexFormat::Uint16,
-        4 => wgpu::IndexFormat::Uint32,
-        _ => panic!("Unsupported index format"),
+    // POSITION 이 없는 primitive 는 그릴 것이 없으므로 건너뛴다 (glTF spec 상 허용됨)
+    let Some(position_acc) = primitive.get(&Semantic::Positions) else {
+        log::warn!("Primitive {} has no POSITION. Skipped", index);
+        return Ok(None);
     };
-
-    let position_acc = primitive
-        .get(&Semantic::Positions)
-        .expect("Failed to get position accessor");
-    let normal_acc = primitive
+    // 모든 attribute 는 (morph target 의 것도) vertex 개수가 같아야 한다
+    let target_accessors = primitive
+        .morph_targets()
+        .flat_map(|target| [target.positions(), target.normals(), target.tangents()])
+        .flatten();
+    for acc in primitive
+        .attributes()
+        .map(|(_, acc)| acc)
+        .chain(target_accessors)
+    {
+        if acc.count() != position_acc.count() {
+            return Err(ImportError::BadAccessor {
+                index: acc.index(),
+                reason: format!(
+                    "Count {} does not match POSITION count {}",
+                    acc.count(),
+                    position_acc.count()
+                ),
+            });
+        }
+    }
+    let mut positions = accessor::read_vec3(&position_acc, root)?;
+    let mut normals = primitive
         .get(&Semantic::Normals)
-        .expect("Failed to get normal accessor");
-    let tex_coord_acc = primitive.get(&Semantic::TexCoords(0));
-
-    let vertex_count = position_acc.count();
-
-    let position_buffer = import_buffer(
-        &position_acc,
-        root,
-        deps,
-        Some(12),
-        "Vertex Position",
-        wgpu::BufferUsages::VERTEX,
-    )
-    .unwrap()
-    .0;
-
-    let normal_buffer = import_buffer(
-        &normal_acc,
-        root,
-        deps,
-        Some(12),
-        "Vertex Normal",
-        wgpu::BufferUsages::VERTEX,
-    )
-    .unwrap()
-    .0;
-
-    let tex_coord_buffer = tex_coord_acc
-        .map(|acc| {
-            import_buffer(
-                &acc,
-                root,
-                deps,
-                Some(8),
-                "Vertex Tex Coord",
-                wgpu::BufferUsages::VERTEX,
+        .map(|acc| accessor::read_vec3(&acc, root))
+        .transpose()?;
+    let mut tex_coords = primitive
+        .get(&Semantic::TexCoords(0))
+        .map(|acc| accessor::read_vec2(&acc, root))
+        .transpose()?;
+    let mut tex_coords_1 = primitive
+        .get(&Semantic::TexCoords(1))
+        .map(|acc| accessor::read_vec2(&acc, root))
+        .transpose()?;
+    let mut tangents = primitive
+        .get(&Semantic::Tangents)
+        .map(|acc| accessor::read_vec4(&acc, root))
+        .transpose()?;
+    let mut colors = primitive
+        .get(&Semantic::Colors(0))
+        .map(|acc| accessor::read_color(&acc, root))
+        .transpose()?;
+    let mut joints = primitive
+        .get(&Semantic::Joints(0))
+        .map(|acc| accessor::read_joints(&acc, root))
+        .transpose()?;
+    let mut joint_weights = primitive
+        .get(&Semantic::Weights(0))
+        .map(|acc| accessor::read_vec4(&acc, root))
+        .transpose()?;
+
+    // morph target 은 mesh 의 기본 weight 로 CPU 에서 적용한다 (TODO: node 의 weight, animation)
+    for (target, weight) in primitive.morph_targets().zip(weights) {
+        if *weight == 0.0 {
+            continue;
+        }
+        if let Some(acc) = target.positions() {
+            accessor::add_weighted(&mut positions, &accessor::read_vec3(&acc, root)?, *weight);
+        }
+        if let (Some(acc), Some(normals)) = (target.normals(), normals.as_mut()) {
+            accessor::add_weighted(normals, &accessor::read_vec3(&acc, root)?, *weight);
+        }
+        if let (Some(acc), Some(tangents)) = (target.tangents(), tangents.as_mut()) {
+            accessor::add_weighted(tangents, &accessor::read_vec3(&acc, root)?, *weight);
+        }
+    }
+
+    // wgpu 에 없는 LineLoop, TriangleFan 은 index 를 만들어서 list 로 바꾼다
+    let (mut topology, mut converted_indices) = match primitive.mode() {
+        Mode::Points => (wgpu::PrimitiveTopology::PointList, None),
+        Mode::Lines => (wgpu::PrimitiveTopology::LineList, None),
+        Mode::LineStrip => (wgpu::PrimitiveTopology::LineStrip, None),
+        Mode::Triangles => (wgpu::PrimitiveTopology::TriangleList, None),
+        Mode::TriangleStrip => (wgpu::PrimitiveTopology::TriangleStrip, None),
+        Mode::LineLoop => {
+            let source = read_or_generate_indices(&primitive, positions.len(), root)?;
+            (
+                wgpu::PrimitiveTopology::LineList,
+                Some(line_loop_to_list(&source)),
             )
-            .unwrap()
-            .0
-        })
-        .unwrap_or_else(|| {
-            log::warn!("Creating null texture coordiates buffer");
-            create_null_texcoord_buffer(deps, vertex_count)
-        });
+        }
+        Mode::TriangleFan => {
+            let source = read_or_generate_indices(&primitive, positions.len(), root)?;
+            (
+                wgpu::PrimitiveTopology::TriangleList,
+                Some(triangle_fan_to_list(&source)),
+            )
+        }
+    };
 
-    Some(MeshPrimitive {
+    let is_triangles = matches!(
+        topology,
+        wgpu::PrimitiveTopology::TriangleList | wgpu::PrimitiveTopology::TriangleStrip
+    );
+    // glTF spec: normal 이 없으면 만들어 쓰고, 이 때 주어진 tangent 는 무시해야 한다
+    if normals.is_none() {
+        tangents = None;
+    }
+    // normal map 이 있는데 tangent 가 없으면 normal map 의 texcoord 로 MikkTSpace 를 돌려서 만든다
+    let normal_tex_coord = primitive.material().normal_texture().map(|t| t.tex_coord());
+    let generate_tangents = is_triangles
+        && tangents.is_none()
+        && match normal_tex_coord {
+            Some(1) => tex_coords_1.is_some(),
+            Some(_) => tex_coords.is_some(),
+            None => false,
+        };
+
+    // 면마다 normal, tangent 가 다를 수 있으므로 index 를 풀어서 vertex 를 면 별로 따로 둔다
+    let mut unindexed = false;
+    if is_triangles && (normals.is_none() || generate_tangents) {
+        let triangle_indices = match converted_indices.take() {
+            Some(indices) => indices,
+            None => {
+                let mut source = read_or_generate_indices(&primitive, positions.len(), root)?;
+                if topology == wgpu::PrimitiveTopology::TriangleStrip {
+                    triangle_strip_to_list(&source)
+                } else {
+                    // 삼각형을 이루지 못하고 남는 vertex 는 GPU 도 그리지 않으므로 버린다
+                    source.truncate(source.len() - source.len() % 3);
+                    source
+                }
+            }
+        };
+        positions = geometry::unindex(&positions, &triangle_indices);
+        normals = normals.map(|n| geometry::unindex(&n, &triangle_indices));
+        tex_coords = tex_coords.map(|t| geometry::unindex(&t, &triangle_indices));
+        tex_coords_1 = tex_coords_1.map(|t| geometry::unindex(&t, &triangle_indices));
+        colors = colors.map(|c| geometry::unindex(&c, &triangle_indices));
+        joints = joints.map(|j| geometry::unindex(&j, &triangle_indices));
+        joint_weights = joint_weights.map(|w| geometry::unindex(&w, &triangle_indices));
+        if normals.is_none() {
+            normals = Some(match options.normal_generation {
+                NormalGeneration::Flat => geometry::flat_normals(&positions),
+                NormalGeneration::Smooth { angle_threshold } => {
+                    geometry::smooth_normals(&positions, angle_threshold.into())
+                }
+            });
+        }
+        if generate_tangents {
+            let tangent_tex_coords = if normal_tex_coord == Some(1) {
+                &tex_coords_1
+            } else {
+                &tex_coords
+            };
+            tangents = Some(geometry::mikktspace_tangents(
+                &positions,
+                normals.as_ref().unwrap(),
+                tangent_tex_coords.as_ref().unwrap(),
+            ));
+        }
+        topology = wgpu::PrimitiveTopology::TriangleList;
+        unindexed = true;
+    }
+
+    let indices = if unindexed {
+        None
+    } else if converted_indices.is_some() {
+        converted_indices.map(Indices::new)
+    } else if primitive.indices().is_some() {
+        Some(Indices::new(read_or_generate_indices(
+            &primitive,
+            positions.len(),
+            root,
+        )?))
+    } else {
+        None
+    };
+
+    // accessor 의 min, max 에는 sparse, morph target 이 반영되어 있지 않으므로 직접 계산한다
+    let bounds = Aabb::from_points(&positions);
+
+    Ok(Some(MeshPrimitive {
         id: Uuid::new_v4(),
+        topology,
+        positions,
+        normals,
+        tex_coords,
+        tex_coords_1,
+        tangents,
+        colors,
+        joints,
+        weights: joint_weights,
+        indices,
         material_id: primitive.material().index().map(|i| material_ids[&i]),
-        position_buffer,
-        normal_buffer,
-        tex_coord_buffer,
-        index_buffer,
-        index_format,
-        num_indices: index_acc.count(),
-        source_info: PrimitiveSourceInfo::Gltf { index: index },
-    })
+        bounds,
+        source_info: PrimitiveSourceInfo::Gltf { index },
+    }))
 }
 
-fn import_buffer(
-    acc: &gltf::Accessor,
+fn read_or_generate_indices(
+    primitive: &gltf::Primitive,
+    vertex_count: usize,
     root: &GltfRoot,
-    deps: &WgpuDeps,
-    assert_stride: Option<usize>,
-    label: &str,
-    usage: wgpu::BufferUsages,
-) -> Option<(wgpu::Buffer, usize)> {
-    let view = acc
-        .view()
-        .expect("Failed to load buffer view from accessor");
-
-    let stride = view.stride().unwrap_or_else(|| acc.size());
-    if let Some(assert_stride) = assert_stride {
-        if stride != assert_stride {
-            panic!("Buffer is not tightly-packed or has invalid type");
+) -> Result<Vec<u32>, ImportError> {
+    let Some(acc) = primitive.indices() else {
+        return Ok((0..(vertex_count as u32)).collect());
+    };
+    // CPU 에서 vertex 를 index 로 참조하므로 범위를 벗어나면 안 된다
+    let indices = accessor::read_indices(&acc, root)?;
+    if let Some(index) = indices.iter().find(|i| **i as usize >= vertex_count) {
+        return Err(ImportError::BadAccessor {
+            index: acc.index(),
+            reason: format!("Index {} is out of vertex range {}", index, vertex_count),
+        });
+    }
+    Ok(indices)
+}
+
+fn line_loop_to_list(indices: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(indices.len() * 2);
+    for i in 0..indices.len() {
+        result.push(indices[i]);
+        result.push(indices[(i + 1) % indices.len()]);
+    }
+    result
+}
+
+// 홀수 번째 삼각형은 winding 이 반대이므로 순서를 바꿔준다
+fn triangle_strip_to_list(indices: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(indices.len().saturating_sub(2) * 3);
+    for i in 0..indices.len().saturating_sub(2) {
+        if i % 2 == 0 {
+            result.extend_from_slice(&[indices[i], indices[i + 1], indices[i + 2]]);
+        } else {
+            result.extend_from_slice(&[indices[i + 1], indices[i], indices[i + 2]]);
         }
     }
+    result
+}
 
-    let offset = view.offset() + acc.offset();
-    let length = acc.size() * acc.count();
-    let buffer = &root.buffers[view.buffer().index()].0;
-    let slice = &buffer[offset..(offset + length)];
-    let wgpu_buffer = deps
-        .device
-        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some(label),
-            contents: slice,
-            usage,
-        });
-    Some((wgpu_buffer, stride))
-}
-
-// TODO: shader permutation or pipeline overridable constants
-fn create_null_texcoord_buffer(deps: &WgpuDeps, count: usize) -> wgpu::Buffer {
-    let mut data = Vec::new();
-    data.resize(count * 2, 0.0f32);
-    let raw_data =
-        unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, data.len() * 4) };
-    deps.device
-        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Null texture coordidates"),
-            contents: raw_data,
-            usage: wgpu::BufferUsages::VERTEX,
-        })
+fn triangle_fan_to_list(indices: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(indices.len().saturating_sub(2) * 3);
+    for i in 1..indices.len().saturating_sub(1) {
+        result.push(indices[0]);
+        result.push(indices[i]);
+        result.push(indices[i + 1]);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    fn only_primitive(model: &ImportedGltf) -> &MeshPrimitive {
+        let mesh = model.meshes.values().next().unwrap();
+        mesh.primitives[0].as_ref().unwrap()
+    }
+
+    // 4 개의 vertex 중 마지막 하나는 삼각형을 이루지 못한다
+    #[test]
+    fn incomplete_triangle_is_dropped_when_generating_normals() {
+        let positions =
+            test_util::f32_bytes(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0]);
+        let indices: Vec<u8> = [0u16, 1, 2, 3]
+            .iter()
+            .flat_map(|i| i.to_le_bytes())
+            .collect();
+        let bin = [positions, indices].concat();
+        let json = |indices: &str| {
+            format!(
+                r#"{{
+                    "bufferViews": [
+                        {{ "buffer": 0, "byteLength": 48 }},
+                        {{ "buffer": 0, "byteOffset": 48, "byteLength": 8 }}
+                    ],
+                    "accessors": [
+                        {{ "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3",
+                           "min": [0, 0, 0], "max": [1, 1, 0] }},
+                        {{ "bufferView": 1, "componentType": 5123, "count": 4, "type": "SCALAR" }}
+                    ],
+                    "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }} {} }}] }}]
+                }}"#,
+                indices
+            )
+        };
+
+        let smooth = NormalGeneration::Smooth {
+            angle_threshold: cgmath::Deg(60.0),
+        };
+        for indices in ["", r#", "indices": 1"#] {
+            for normal_generation in [NormalGeneration::Flat, smooth] {
+                let model =
+                    test_util::prepare(&json(indices), &bin, &ImportOptions { normal_generation });
+                let primitive = only_primitive(&model);
+                assert_eq!(primitive.positions.len(), 3);
+                assert_eq!(primitive.normals.as_ref().unwrap().len(), 3);
+                assert_eq!(primitive.normals.as_ref().unwrap()[0], [0.0, 0.0, 1.0]);
+            }
+        }
+    }
+
+    // 삼각형 하나에 normal 이 있어서 index 를 풀지 않는다
+    fn indexed_triangle(component_type: u32, indices: &[u8]) -> ImportedGltf {
+        let bin = [
+            test_util::f32_bytes(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]),
+            test_util::f32_bytes(&[0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0]),
+            indices.to_vec(),
+        ]
+        .concat();
+        let json = format!(
+            r#"{{
+                "bufferViews": [
+                    {{ "buffer": 0, "byteLength": 72 }},
+                    {{ "buffer": 0, "byteOffset": 72, "byteLength": {} }}
+                ],
+                "accessors": [
+                    {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                       "min": [0, 0, 0], "max": [1, 1, 0] }},
+                    {{ "bufferView": 0, "byteOffset": 36, "componentType": 5126, "count": 3, "type": "VEC3" }},
+                    {{ "bufferView": 1, "componentType": {}, "count": 3, "type": "SCALAR" }}
+                ],
+                "meshes": [{{
+                    "primitives": [{{ "attributes": {{ "POSITION": 0, "NORMAL": 1 }}, "indices": 2 }}]
+                }}]
+            }}"#,
+            indices.len(),
+            component_type
+        );
+        test_util::prepare(&json, &bin, &ImportOptions::default())
+    }
+
+    #[test]
+    fn index_width_is_kept_when_it_fits() {
+        let u16_indices: Vec<u8> = [0u16, 1, 2].iter().flat_map(|i| i.to_le_bytes()).collect();
+        let u32_indices: Vec<u8> = [0u32, 1, 2].iter().flat_map(|i| i.to_le_bytes()).collect();
+        for (component_type, bytes) in [
+            (5121, vec![0, 1, 2]),
+            (5123, u16_indices),
+            (5125, u32_indices),
+        ] {
+            let model = indexed_triangle(component_type, &bytes);
+            let indices = only_primitive(&model).indices.as_ref().unwrap();
+            assert_eq!(indices, &Indices::U16(vec![0, 1, 2]));
+            assert_eq!(indices.format(), wgpu::IndexFormat::Uint16);
+        }
+    }
+
+    #[test]
+    fn morph_target_count_must_match_position_count() {
+        let bin = test_util::f32_bytes(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        let root = test_util::load(
+            r#"{
+                "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+                "accessors": [
+                    { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                      "min": [0, 0, 0], "max": [1, 1, 0] },
+                    { "bufferView": 0, "componentType": 5126, "count": 2, "type": "VEC3",
+                      "min": [0, 0, 0], "max": [1, 0, 0] }
+                ],
+                "meshes": [{
+                    "primitives": [{ "attributes": { "POSITION": 0 }, "targets": [{ "POSITION": 1 }] }],
+                    "weights": [1.0]
+                }]
+            }"#,
+            &bin,
+        );
+        let result = prepare_gltf(root, &ImportOptions::default(), &mut |_| Ok(()));
+        assert!(matches!(
+            result,
+            Err(ImportError::BadAccessor { index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn progress_can_cancel_before_buffers_are_read() {
+        let bin = test_util::f32_bytes(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        let glb = test_util::glb(
+            r#"{
+                "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+                "accessors": [
+                    { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                      "min": [0, 0, 0], "max": [1, 1, 0] }
+                ],
+                "meshes": [
+                    { "primitives": [{ "attributes": { "POSITION": 0 } }] },
+                    { "primitives": [{ "attributes": { "POSITION": 0 } }] }
+                ]
+            }"#,
+            &bin,
+        );
+
+        let mut reported = Vec::new();
+        let mut record = |p: LoadProgress| {
+            reported.push((p.stage, p.completed, p.total));
+            Ok(())
+        };
+        let root = load_gltf_slice_with_progress(&glb, &mut record).unwrap();
+        prepare_gltf(root, &ImportOptions::default(), &mut record).unwrap();
+        assert_eq!(
+            reported,
+            vec![
+                (LoadStage::LoadingBuffers, 0, 1),
+                (LoadStage::ProcessingMeshes, 0, 2),
+                (LoadStage::ProcessingMeshes, 1, 2),
+            ]
+        );
+
+        let result = load_gltf_slice_with_progress(&glb, &mut |_| Err(ImportError::Cancelled));
+        assert!(matches!(result, Err(ImportError::Cancelled)));
+    }
 }
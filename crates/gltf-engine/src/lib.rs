@@ -1,16 +1,34 @@
+mod accessor;
 mod camera;
+mod geometry;
 mod image_util;
 mod import;
-mod mesh;
+mod loader;
+pub mod mesh;
 pub mod model;
+mod outline;
+mod picking;
+mod pipeline;
+mod resource_cache;
+#[cfg(test)]
+mod test_util;
 mod texture;
+mod uniform;
 
 use crate::camera::CameraController;
+use crate::resource_cache::{ResourceCache, WgpuDeps};
+use crate::uniform::{CameraUniform, NodeUniform};
 use cgmath::*;
 use std::collections::HashSet;
 use uuid::Uuid;
+pub use cgmath;
+pub use import::{
+    load_gltf, load_gltf_slice, prepare_gltf, GltfRoot, ImportError, ImportOptions,
+    NormalGeneration, PreparedGltf,
+};
+pub use loader::{LoadEvent, LoadProgress, LoadSource, LoadStage, LoadTask};
+pub use picking::{pick, PickHit, Ray};
 pub use wgpu;
-use wgpu::include_wgsl;
 use wgpu::util::DeviceExt;
 
 // Renderer 는 Window 나 UI 에 대해서는 몰라야 한다
@@ -30,7 +48,7 @@ use wgpu::util::DeviceExt;
 
 // 특이사항 - vertex buffer 의 레이아웃은 사전에 알 수 없다. gltf 파일 마다 다를 수 있다. 단 position, normal, texcoord 가 있다는 가정 정도는 해도 괜찮을듯 (정 없으면 만들어 넣으면 되니까)
 // Vertex struct 를 굳이 만들 필요도 없음
-// 이 때 vertex layout 이 다른 유형마다 각각 Render pipeline 을 만들어주어야 함. shader 코드는 같아도 됨
+// 이 때 vertex layout 이 다른 유형마다 각각 Render pipeline 을 만들어주어야 함. shader 코드는 같아도 됨 (pipeline.rs 의 PipelineCache)
 
 const ENGINE_COLOR_LABEL: &str = "engine color target";
 const ENGINE_DEPTH_LABEL: &str = "engine depth target";
@@ -122,18 +140,20 @@ pub struct Engine {
     target_height: u32,
 
     // pipeline resource
-    render_pipeline: wgpu::RenderPipeline,
+    pipeline_cache: pipeline::PipelineCache,
+    import_options: ImportOptions,
     color_texture: texture::Texture,
     depth_texture: texture::Texture,
 
-    model_root: model::ImportedGltf,
+    // 아무것도 불러오지 않았으면 None
+    model_root: Option<model::ImportedGltf>,
+    // model_root 의 GPU 자원
+    resources: ResourceCache,
 
     // layout
     #[allow(dead_code)]
     camera_bind_group_layout: wgpu::BindGroupLayout,
-    #[allow(dead_code)]
     node_bind_group_layout: wgpu::BindGroupLayout,
-    #[allow(dead_code)]
     material_bind_group_layout: wgpu::BindGroupLayout,
 
     // camera state
@@ -147,109 +167,18 @@ pub struct Engine {
     camera_bind_group: wgpu::BindGroup,
 
     // etc
-    #[allow(dead_code)]
     white_texture: texture::Texture,
 
-    pending_nodes: Vec<Uuid>,
-}
-
-#[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct NodeUniform {
-    model_mat: [[f32; 4]; 4],
-    normal_mat: [[f32; 4]; 4],
-}
-
-#[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct MaterialUniform {
-    base_color_factor: [f32; 4],
-    emissive_factor: [f32; 3],
-    _pad: f32,
-}
-
-#[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct CameraUniform {
-    view_position: [f32; 4],
-    view_front: [f32; 4],
-    view_proj: [[f32; 4]; 4],
-}
-
-impl CameraUniform {
-    fn new() -> Self {
-        Self {
-            view_position: cgmath::Vector4::zero().into(),
-            view_front: cgmath::Vector4::unit_x().into(),
-            view_proj: cgmath::Matrix4::identity().into(),
-        }
-    }
-
-    fn update_view_proj(&mut self, camera: &camera::Camera, projection: &camera::Projection) {
-        self.view_position = camera.position.to_homogeneous().into();
-        let f = camera.front();
-        self.view_front = Vector4::new(f.x, f.y, f.z, 0.0).into();
-        self.view_proj = (projection.calc_matrix() * camera.calc_matrix()).into();
-    }
-}
-
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct VertexPosition([f32; 3]);
-
-impl VertexPosition {
-    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
-        use std::mem;
-
-        wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[wgpu::VertexAttribute {
-                offset: 0,
-                shader_location: 0,
-                format: wgpu::VertexFormat::Float32x3,
-            }],
-        }
-    }
-}
+    // update 에서 계산한 world transform 을 render 에서 정렬하는 데 사용한다
+    pending_nodes: Vec<(Uuid, Matrix4<f32>)>,
 
-struct VertexNormal([f32; 3]);
-
-impl VertexNormal {
-    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
-        use std::mem;
-
-        wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[wgpu::VertexAttribute {
-                offset: 0,
-                shader_location: 1,
-                format: wgpu::VertexFormat::Float32x3,
-            }],
-        }
-    }
+    // set_selection 으로 받은 node. 자식 node 도 함께 외곽선을 그린다
+    selection: HashSet<Uuid>,
+    outline: outline::OutlineRenderer,
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct VertexTexCoord([f32; 2]);
-
-impl VertexTexCoord {
-    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
-        use std::mem;
-
-        wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[wgpu::VertexAttribute {
-                offset: 0,
-                shader_location: 2,
-                format: wgpu::VertexFormat::Float32x2,
-            }],
-        }
-    }
-}
+// base color, metallic-roughness, normal, occlusion, emissive 순서로 (texture, sampler) 가 binding 된다
+const MATERIAL_TEXTURE_COUNT: u32 = 5;
 
 impl Engine {
     pub async fn new(
@@ -274,64 +203,44 @@ impl Engine {
                 label: Some("node_bind_group_layout"),
             });
 
+        let mut material_layout_entries = vec![wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }];
+        for i in 0..MATERIAL_TEXTURE_COUNT {
+            material_layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 1 + i * 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            });
+            material_layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 2 + i * 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            });
+        }
+
         let material_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
+                entries: &material_layout_entries,
                 label: Some("material_bind_group_layout"),
             });
 
-        // TODO: main 으로 빼기
-        let gltf_root = {
-            let args = std::env::args().collect::<Vec<_>>();
-            let (document, buffers, images) = gltf::import(&args[1]).unwrap();
-            import::GltfRoot {
-                document,
-                buffers,
-                images,
-            }
-        };
-
         let white_image = image_util::white_image();
         let white_texture =
-            texture::Texture::from_image(&device, &queue, &white_image, Some("White")).unwrap();
-
-        let model_root = import::import_gltf(
-            &gltf_root,
-            &import::WgpuDeps {
-                device: &device,
-                queue: &queue,
-                node_uniform_layout: &node_bind_group_layout,
-                material_uniform_layout: &material_bind_group_layout,
-                white_texture: &white_texture,
-            },
-        );
+            texture::Texture::from_image(device, queue, &white_image, Some("White")).unwrap();
 
         let camera = camera::Camera::new((0.0, 5.0, 10.0), cgmath::Deg(-90.0), cgmath::Deg(-20.0));
         let projection = camera::Projection::new(width, height, cgmath::Deg(45.0), 0.1, 100.0);
@@ -370,12 +279,10 @@ impl Engine {
             label: Some("camera_bind_group"),
         });
 
-        let shader = device.create_shader_module(include_wgsl!("shader.wgsl"));
-
         let color_texture =
-            texture::Texture::create_color_texture(&device, width, height, ENGINE_COLOR_LABEL);
+            texture::Texture::create_color_texture(device, width, height, ENGINE_COLOR_LABEL);
         let depth_texture =
-            texture::Texture::create_depth_texture(&device, width, height, ENGINE_DEPTH_LABEL);
+            texture::Texture::create_depth_texture(device, width, height, ENGINE_DEPTH_LABEL);
 
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -388,53 +295,15 @@ impl Engine {
                 push_constant_ranges: &[],
             });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[
-                    VertexPosition::desc(),
-                    VertexNormal::desc(),
-                    VertexTexCoord::desc(),
-                ],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: target_format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-
-                polygon_mode: wgpu::PolygonMode::Fill,
-
-                unclipped_depth: false,
-
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: texture::Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-        });
+        let pipeline_cache = pipeline::PipelineCache::new(render_pipeline_layout, target_format);
+        let outline = outline::OutlineRenderer::new(
+            device,
+            &camera_bind_group_layout,
+            &node_bind_group_layout,
+            target_format,
+            width,
+            height,
+        );
 
         Self {
             fly_cam_session: FlyCamSession {
@@ -442,8 +311,10 @@ impl Engine {
             },
             target_width: width,
             target_height: height,
-            render_pipeline,
-            model_root,
+            pipeline_cache,
+            import_options: ImportOptions::default(),
+            model_root: None,
+            resources: ResourceCache::default(),
             camera,
             projection,
             camera_controller,
@@ -457,6 +328,8 @@ impl Engine {
             depth_texture,
             white_texture,
             pending_nodes: Vec::new(),
+            selection: HashSet::new(),
+            outline,
         }
     }
 
@@ -466,30 +339,128 @@ impl Engine {
         if changed {
             self.projection.resize(width, height);
             self.color_texture =
-                texture::Texture::create_color_texture(&device, width, height, ENGINE_COLOR_LABEL);
+                texture::Texture::create_color_texture(device, width, height, ENGINE_COLOR_LABEL);
             self.depth_texture =
-                texture::Texture::create_depth_texture(&device, width, height, ENGINE_DEPTH_LABEL);
+                texture::Texture::create_depth_texture(device, width, height, ENGINE_DEPTH_LABEL);
+            self.outline.resize(device, width, height);
             self.target_width = width;
             self.target_height = height;
         }
         changed
     }
 
-    pub fn model_root(&self) -> &model::ImportedGltf {
-        &self.model_root
+    pub fn model_root(&self) -> Option<&model::ImportedGltf> {
+        self.model_root.as_ref()
+    }
+
+    pub fn model_root_mut(&mut self) -> Option<&mut model::ImportedGltf> {
+        self.model_root.as_mut()
     }
 
-    pub fn model_root_mut(&mut self) -> &mut model::ImportedGltf {
-        &mut self.model_root
+    pub fn load_path(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), ImportError> {
+        let gltf_root = import::load_gltf(path)?;
+        self.load_gltf_root(device, queue, gltf_root)
+    }
+
+    // GLB 또는 glTF (JSON) 파일의 내용
+    pub fn load_slice(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+    ) -> Result<(), ImportError> {
+        let gltf_root = import::load_gltf_slice(bytes)?;
+        self.load_gltf_root(device, queue, gltf_root)
+    }
+
+    fn load_gltf_root(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        gltf_root: import::GltfRoot,
+    ) -> Result<(), ImportError> {
+        let prepared = import::prepare_gltf(gltf_root, &self.import_options, &mut |_| Ok(()))?;
+        self.load_prepared(device, queue, prepared)
+    }
+
+    // render thread 를 막지 않도록 GPU 가 필요 없는 작업은 worker thread 에서 한다
+    // LoadEvent::Finished 로 받은 결과를 load_prepared 로 넘긴다
+    pub fn spawn_load_task(&self, source: LoadSource) -> LoadTask {
+        LoadTask::spawn(source, self.import_options)
+    }
+
+    // GPU 자원만 만든다. 불러오기에 실패하면 기존 model 은 그대로 남는다
+    pub fn load_prepared(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        prepared: PreparedGltf,
+    ) -> Result<(), ImportError> {
+        let mut resources = ResourceCache::default();
+        resources.sync(
+            &prepared.model,
+            &WgpuDeps {
+                device,
+                queue,
+                node_uniform_layout: &self.node_bind_group_layout,
+                material_uniform_layout: &self.material_bind_group_layout,
+                white_texture: &self.white_texture,
+            },
+            &mut self.pipeline_cache,
+        )?;
+        self.unload();
+        self.model_root = Some(prepared.model);
+        self.resources = resources;
+        Ok(())
+    }
+
+    // 불러온 model 에 없는 scene 이면 무시한다
+    pub fn set_active_scene(&mut self, scene_id: Uuid) {
+        let Some(model_root) = &mut self.model_root else {
+            return;
+        };
+        if model_root.scenes.contains_key(&scene_id) {
+            model_root.active_scene_id = Some(scene_id);
+        } else {
+            log::warn!("Scene {} does not exist", scene_id);
+        }
+    }
+
+    // buffer, texture 등의 GPU 자원은 drop 될 때 해제된다
+    // pipeline 은 vertex 구성 별로 만들어지므로 다음 model 에서 재사용할 수 있도록 남겨둔다
+    pub fn unload(&mut self) {
+        self.pending_nodes.clear();
+        self.selection.clear();
+        self.resources.clear();
+        self.model_root = None;
+    }
+
+    // 선택된 node 와 그 자식 node 들의 외곽선을 그린다. 빈 slice 를 넘기면 선택을 해제한다
+    // 불러온 model 에 없는 node 는 무시한다
+    pub fn set_selection(&mut self, node_ids: &[Uuid]) {
+        self.selection = node_ids.iter().copied().collect();
+    }
+
+    // x, y 는 viewport 안의 위치 (왼쪽 위가 (0, 0), 오른쪽 아래가 (1, 1))
+    // 마지막으로 update 했을 때의 camera 로 본 모습을 기준으로 한다
+    pub fn pick(&self, x: f32, y: f32) -> Option<PickHit> {
+        let model_root = self.model_root.as_ref()?;
+        let view_proj = self.projection.calc_matrix() * self.camera.calc_matrix();
+        let ray = Ray::from_viewport(view_proj, x, y)?;
+        pick(model_root, &ray)
     }
 
-    // TODO: eframe 대응
     pub fn input(&mut self, event: &InputEvent) -> bool {
         self.fly_cam_session
             .handle_input(event, &mut self.camera_controller)
     }
 
-    pub fn update(&mut self, queue: &wgpu::Queue) {
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
         self.camera_controller.update_direction(&mut self.camera);
         if let Some(session) = self.fly_cam_session.position_session() {
             session.prev_time = session.now;
@@ -509,47 +480,44 @@ impl Engine {
 
         self.pending_nodes.clear();
 
-        {
-            let mut node_stack: Vec<(&model::Node, Matrix4<f32>)> = Vec::new();
-
-            let scene = self.model_root().default_scene();
-
-            for root_node_ids in &scene.nodes {
-                node_stack.push((&self.model_root.nodes[root_node_ids], Matrix4::identity()));
-            }
-
-            while let Some((node, upper_transform)) = node_stack.pop() {
-                // TODO: 매번 write_buffer 할 필요 없음
-                // TODO: cgmath::Matrix4 가 bytemuck 이랑 연동되면 좋을텐데 -> nalgebra?
-
-                if node.transform.scale.product() == 0.0 {
-                    continue;
-                }
+        let Some(model_root) = &self.model_root else {
+            return;
+        };
+        // model 이 바뀌었으면 GPU 자원을 맞춘다
+        let sync_result = self.resources.sync(
+            model_root,
+            &WgpuDeps {
+                device,
+                queue,
+                node_uniform_layout: &self.node_bind_group_layout,
+                material_uniform_layout: &self.material_bind_group_layout,
+                white_texture: &self.white_texture,
+            },
+            &mut self.pipeline_cache,
+        );
+        if let Err(e) = sync_result {
+            log::error!("Failed to sync GPU resources: {}", e);
+        }
 
-                let transform = upper_transform * node.transform.matrix();
-                let rs = Matrix3::from_cols(
-                    transform.x.truncate(),
-                    transform.y.truncate(),
-                    transform.z.truncate(),
-                );
-                let node_uniform = NodeUniform {
-                    model_mat: transform.into(),
-                    normal_mat: Matrix4::from(rs.invert().unwrap().transpose()).into(),
-                };
-                queue.write_buffer(
-                    &node.uniform_buffer,
-                    0,
-                    bytemuck::cast_slice(&[node_uniform]),
-                );
-
-                self.pending_nodes.push(node.id);
-
-                // visit children
-                for child_id in &node.children {
-                    let child = &self.model_root.nodes[child_id];
-                    node_stack.push((child, transform))
-                }
-            }
+        for (node, transform) in model_root.visible_nodes() {
+            // TODO: 매번 write_buffer 할 필요 없음
+            // TODO: cgmath::Matrix4 가 bytemuck 이랑 연동되면 좋을텐데 -> nalgebra?
+            let rs = Matrix3::from_cols(
+                transform.x.truncate(),
+                transform.y.truncate(),
+                transform.z.truncate(),
+            );
+            let node_uniform = NodeUniform {
+                model_mat: transform.into(),
+                normal_mat: Matrix4::from(rs.invert().unwrap().transpose()).into(),
+            };
+            queue.write_buffer(
+                &self.resources.nodes[&node.id].uniform_buffer,
+                0,
+                bytemuck::cast_slice(&[node_uniform]),
+            );
+
+            self.pending_nodes.push((node.id, transform));
         }
     }
 
@@ -585,53 +553,117 @@ impl Engine {
                     stencil_ops: None,
                 }),
             });
-            render_pass.set_pipeline(&self.render_pipeline);
             render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
 
-            for node_id in &self.pending_nodes {
-                let node = &self.model_root.nodes[node_id];
-
-                if let Some(mesh_id) = node.mesh_id {
-                    let mesh = &self.model_root.meshes[&mesh_id];
-                    for primitive in mesh.primitives.iter() {
-                        if primitive.is_none() {
-                            continue;
+            let mut opaque_items = Vec::new();
+            let mut blend_items = Vec::new();
+
+            if let Some(model_root) = &self.model_root {
+                for (node_id, transform) in &self.pending_nodes {
+                    let node = &model_root.nodes[node_id];
+
+                    if let Some(mesh_id) = node.mesh_id {
+                        let mesh = &model_root.meshes[&mesh_id];
+                        for primitive in mesh.primitives.iter() {
+                            if primitive.is_none() {
+                                continue;
+                            }
+                            let primitive = primitive.as_ref().unwrap();
+
+                            // material 이 없으면 glTF 의 기본 material (opaque) 로 그린다
+                            let (material_resource, alpha_mode) = match primitive.material_id {
+                                Some(id) => (
+                                    &self.resources.materials[&id],
+                                    model_root.materials[&id].alpha_mode,
+                                ),
+                                None => (
+                                    self.resources.default_material.as_ref().unwrap(),
+                                    model::AlphaMode::Opaque,
+                                ),
+                            };
+                            let primitive_resource = &self.resources.primitives[&primitive.id];
+
+                            let item = DrawItem {
+                                node: &self.resources.nodes[node_id],
+                                primitive: primitive_resource,
+                                material: material_resource,
+                                pipeline: self.pipeline_cache.get(&primitive_resource.pipeline_key),
+                            };
+                            if alpha_mode == model::AlphaMode::Blend {
+                                let center = transform
+                                    .transform_point(Point3::from_vec(primitive.bounds.center()));
+                                let depth = (center - self.camera.position).dot(self.camera.front());
+                                blend_items.push((depth, item));
+                            } else {
+                                opaque_items.push(item);
+                            }
                         }
-                        let primitive = primitive.as_ref().unwrap();
-
-                        // TODO: default material
-                        let material_id = if let Some(id) = primitive.material_id {
-                            id
-                        } else {
-                            continue;
-                        };
-                        let material = &self.model_root.materials[&material_id];
-
-                        let mesh::MeshPrimitive {
-                            position_buffer,
-                            normal_buffer,
-                            tex_coord_buffer,
-                            index_buffer,
-                            index_format,
-                            num_indices,
-                            ..
-                        } = &primitive;
-
-                        render_pass.set_bind_group(2, &node.uniform_bind_group, &[]);
-                        render_pass.set_bind_group(0, &material.material_bind_group, &[]);
-                        render_pass.set_vertex_buffer(0, position_buffer.slice(..));
-                        render_pass.set_vertex_buffer(1, normal_buffer.slice(..));
-                        render_pass.set_vertex_buffer(2, tex_coord_buffer.slice(..));
-                        render_pass.set_index_buffer(index_buffer.slice(..), *index_format);
-                        render_pass.draw_indexed(0..(*num_indices as u32), 0, 0..1);
                     }
                 }
             }
+
+            for item in &opaque_items {
+                item.draw(&mut render_pass);
+            }
+
+            // 반투명한 primitive 는 opaque 를 다 그린 다음, 먼 것부터 (back-to-front) 그린다
+            blend_items.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+            for (_, item) in &blend_items {
+                item.draw(&mut render_pass);
+            }
         }
+        self.render_outline(device, &mut encoder);
         let command_buffer = encoder.finish();
         Ok(command_buffer)
     }
 
+    fn render_outline(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+        let Some(model_root) = &self.model_root else {
+            return;
+        };
+        if self.selection.is_empty() {
+            return;
+        }
+
+        let mut highlighted = HashSet::new();
+        let mut stack: Vec<Uuid> = self.selection.iter().copied().collect();
+        while let Some(node_id) = stack.pop() {
+            let Some(node) = model_root.nodes.get(&node_id) else {
+                continue;
+            };
+            if highlighted.insert(node_id) {
+                stack.extend(node.children.iter().copied());
+            }
+        }
+
+        // 화면에 그려진 primitive 만
+        let mut items = Vec::new();
+        for (node_id, _) in &self.pending_nodes {
+            if !highlighted.contains(node_id) {
+                continue;
+            }
+            let Some(mesh_id) = model_root.nodes[node_id].mesh_id else {
+                continue;
+            };
+            let primitives = model_root.meshes[&mesh_id].primitives.iter().flatten();
+            for primitive in primitives {
+                let primitive_resource = &self.resources.primitives[&primitive.id];
+                self.outline
+                    .prepare(device, &primitive_resource.pipeline_key);
+                items.push((&self.resources.nodes[node_id], primitive_resource));
+            }
+        }
+        if items.is_empty() {
+            return;
+        }
+        self.outline.render(
+            encoder,
+            &self.color_texture.view,
+            &self.camera_bind_group,
+            &items,
+        );
+    }
+
     pub fn end_frame(&mut self) {
         // unimplemented!();
     }
@@ -640,11 +672,40 @@ impl Engine {
         &mut self.camera_controller
     }
 
+    // 이후에 불러오는 glTF 에 적용된다
+    pub fn import_options_mut(&mut self) -> &mut ImportOptions {
+        &mut self.import_options
+    }
+
     pub fn color_texture_view(&self) -> &wgpu::TextureView {
         &self.color_texture.view
     }
 }
 
+struct DrawItem<'a> {
+    node: &'a resource_cache::NodeResource,
+    primitive: &'a resource_cache::PrimitiveResource,
+    material: &'a resource_cache::MaterialResource,
+    pipeline: &'a wgpu::RenderPipeline,
+}
+
+impl<'a> DrawItem<'a> {
+    fn draw(&self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_pipeline(self.pipeline);
+        render_pass.set_bind_group(2, &self.node.uniform_bind_group, &[]);
+        render_pass.set_bind_group(0, &self.material.material_bind_group, &[]);
+        for (slot, buffer) in self.primitive.vertex_buffers.iter().enumerate() {
+            render_pass.set_vertex_buffer(slot as u32, buffer.slice(..));
+        }
+        if let Some(indices) = &self.primitive.indices {
+            render_pass.set_index_buffer(indices.buffer.slice(..), indices.format);
+            render_pass.draw_indexed(0..(indices.count as u32), 0, 0..1);
+        } else {
+            render_pass.draw(0..(self.primitive.num_vertices as u32), 0..1);
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum InputEvent {
     KeyPressing(AbstractKey),
@@ -0,0 +1,119 @@
+use crate::import::{self, ImportError, ImportOptions, PreparedGltf};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+
+// 파일 읽기, image 변환, mesh 처리처럼 GPU 가 필요 없는 작업은 worker thread 에서 한다
+// 끝나면 PreparedGltf 를 받아서 render thread 에서 Engine::load_prepared 로 GPU 에 올린다
+
+pub enum LoadSource {
+    Path(PathBuf),
+    // GLB 또는 glTF (JSON) 파일의 내용
+    Slice(Vec<u8>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoadStage {
+    Parsing,
+    LoadingBuffers,
+    DecodingImages,
+    // 변환, mipmap 생성
+    ProcessingImages,
+    ProcessingMeshes,
+}
+
+impl LoadStage {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LoadStage::Parsing => "Parsing",
+            LoadStage::LoadingBuffers => "Loading buffers",
+            LoadStage::DecodingImages => "Decoding images",
+            LoadStage::ProcessingImages => "Processing images",
+            LoadStage::ProcessingMeshes => "Processing meshes",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct LoadProgress {
+    pub stage: LoadStage,
+    pub completed: usize,
+    pub total: usize,
+}
+
+impl LoadProgress {
+    // 단계 안에서의 진행률 (0.0 ~ 1.0)
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.completed as f32 / self.total as f32
+        }
+    }
+}
+
+pub enum LoadEvent {
+    Progress(LoadProgress),
+    // PreparedGltf 는 크기가 커서 Box 로 넘긴다
+    Finished(Result<Box<PreparedGltf>, ImportError>),
+}
+
+pub struct LoadTask {
+    events: mpsc::Receiver<LoadEvent>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl LoadTask {
+    pub fn spawn(source: LoadSource, options: ImportOptions) -> Self {
+        let (sender, events) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let worker_cancelled = cancelled.clone();
+
+        std::thread::Builder::new()
+            .name("glTF loader".to_owned())
+            .spawn(move || {
+                // 받는 쪽이 사라졌으면 (task 가 drop 됨) 더 진행할 필요가 없으므로 취소로 본다
+                let mut progress = |progress: LoadProgress| {
+                    if worker_cancelled.load(Ordering::Relaxed)
+                        || sender.send(LoadEvent::Progress(progress)).is_err()
+                    {
+                        return Err(ImportError::Cancelled);
+                    }
+                    Ok(())
+                };
+                let result = progress(LoadProgress {
+                    stage: LoadStage::Parsing,
+                    completed: 0,
+                    total: 1,
+                })
+                .and_then(|_| match source {
+                    LoadSource::Path(path) => import::load_gltf_with_progress(&path, &mut progress),
+                    LoadSource::Slice(bytes) => {
+                        import::load_gltf_slice_with_progress(&bytes, &mut progress)
+                    }
+                })
+                .and_then(|root| import::prepare_gltf(root, &options, &mut progress))
+                .map(Box::new);
+                let _ = sender.send(LoadEvent::Finished(result));
+            })
+            .expect("Failed to spawn glTF loader thread");
+
+        Self { events, cancelled }
+    }
+
+    // worker 는 다음 단계로 넘어갈 때 취소 여부를 확인한다. 취소되면 Finished(Err(Cancelled)) 가 온다
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    // 쌓인 event 를 하나씩 꺼낸다. render loop 에서 매 frame 호출한다
+    pub fn poll_event(&self) -> Option<LoadEvent> {
+        self.events.try_recv().ok()
+    }
+}
+
+impl Drop for LoadTask {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
@@ -1,3 +1,5 @@
+use crate::pipeline::VertexAttributes;
+use cgmath::Vector3;
 use uuid::Uuid;
 
 pub enum MeshSourceInfo {
@@ -7,6 +9,9 @@ pub enum MeshSourceInfo {
 
 pub struct Mesh {
     pub id: Uuid,
+    pub name: Option<String>,
+    // JSON 원문
+    pub extras: Option<String>,
     pub primitives: Vec<Option<MeshPrimitive>>,
     pub source_info: MeshSourceInfo,
 }
@@ -18,20 +23,152 @@ impl Mesh {
         };
         index
     }
+
+    // 이름이 없으면 glTF index 로 대신한다
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("Mesh {}", self.gltf_index()),
+        }
+    }
 }
 
 pub enum PrimitiveSourceInfo {
     Gltf { index: usize },
 }
 
+// vertex attribute 는 모두 vertex 개수만큼 있다
+// GPU buffer 는 renderer 의 ResourceCache 가 id 별로 만든다. vertex 데이터를 바꾸려면 새 id 의 primitive 로 교체한다
 pub struct MeshPrimitive {
     pub id: Uuid,
-    pub position_buffer: wgpu::Buffer,
-    pub normal_buffer: wgpu::Buffer,
-    pub tex_coord_buffer: wgpu::Buffer,
-    pub index_buffer: wgpu::Buffer,
-    pub num_indices: usize,
-    pub index_format: wgpu::IndexFormat,
+    pub topology: wgpu::PrimitiveTopology,
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Option<Vec<[f32; 3]>>,
+    pub tex_coords: Option<Vec<[f32; 2]>>,
+    pub tex_coords_1: Option<Vec<[f32; 2]>>,
+    // w 는 bitangent 의 부호
+    pub tangents: Option<Vec<[f32; 4]>>,
+    // linear RGBA
+    pub colors: Option<Vec<[f32; 4]>>,
+    // skinning 은 아직 적용하지 않지만 shader 입력으로는 넘긴다
+    pub joints: Option<Vec<[u16; 4]>>,
+    pub weights: Option<Vec<[f32; 4]>>,
+    // index 가 없는 primitive 는 vertex 순서대로 그린다
+    pub indices: Option<Indices>,
     pub material_id: Option<Uuid>,
+    pub bounds: Aabb,
     pub source_info: PrimitiveSourceInfo,
 }
+
+impl MeshPrimitive {
+    pub fn gltf_index(&self) -> usize {
+        let PrimitiveSourceInfo::Gltf { index } = self.source_info;
+        index
+    }
+
+    pub fn vertex_attributes(&self) -> VertexAttributes {
+        VertexAttributes {
+            normal: self.normals.is_some(),
+            tex_coord_0: self.tex_coords.is_some(),
+            tex_coord_1: self.tex_coords_1.is_some(),
+            tangent: self.tangents.is_some(),
+            color: self.colors.is_some(),
+            joints_weights: self.joints.is_some() && self.weights.is_some(),
+        }
+    }
+}
+
+// GPU 의 index buffer 와 같은 형식으로 저장한다
+// wgpu 에는 u8 index 가 없으므로 glTF 의 unsigned byte index 는 u16 으로 넓힌다
+#[derive(Debug, Clone, PartialEq)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    // 가장 큰 index 가 들어가면 u16 으로 줄인다
+    // 0xffff 는 strip 에서 primitive restart 로 해석되므로 u16 으로 쓰지 않는다
+    pub fn new(indices: Vec<u32>) -> Self {
+        if indices.iter().all(|i| *i < u16::MAX as u32) {
+            Indices::U16(indices.into_iter().map(|i| i as u16).collect())
+        } else {
+            Indices::U32(indices)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, i: usize) -> u32 {
+        match self {
+            Indices::U16(indices) => indices[i] as u32,
+            Indices::U32(indices) => indices[i],
+        }
+    }
+
+    pub fn format(&self) -> wgpu::IndexFormat {
+        match self {
+            Indices::U16(_) => wgpu::IndexFormat::Uint16,
+            Indices::U32(_) => wgpu::IndexFormat::Uint32,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Indices::U16(indices) => bytemuck::cast_slice(indices),
+            Indices::U32(indices) => bytemuck::cast_slice(indices),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    pub fn from_points(points: &[[f32; 3]]) -> Self {
+        if points.is_empty() {
+            return Self {
+                min: Vector3::new(0.0, 0.0, 0.0),
+                max: Vector3::new(0.0, 0.0, 0.0),
+            };
+        }
+        let mut min = Vector3::from(points[0]);
+        let mut max = min;
+        for point in points {
+            for i in 0..3 {
+                min[i] = min[i].min(point[i]);
+                max[i] = max[i].max(point[i]);
+            }
+        }
+        Self { min, max }
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_indices_use_u32() {
+        let indices = Indices::new(vec![0, 1, u16::MAX as u32]);
+        assert_eq!(indices.format(), wgpu::IndexFormat::Uint32);
+        assert_eq!(indices.get(2), u16::MAX as u32);
+        assert_eq!(indices.as_bytes().len(), 12);
+    }
+}
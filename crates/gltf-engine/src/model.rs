@@ -16,6 +16,8 @@ use uuid::Uuid;
 - mesh
 
 texture bind group 은 material 에 있는게 맞...나?
+-> 아님. model 은 CPU 데이터만 가지고, GPU 자원은 renderer 의 ResourceCache 가 id 별로 따로 관리한다 (resource_cache.rs)
+  GPU 없이도 model 을 만들고, 읽고, 고칠 수 있다
 */
 
 // 여러가지 use case 들이 생각나서 설계를 할 때 머리가 복잡해지네
@@ -28,20 +30,63 @@ texture bind group 은 material 에 있는게 맞...나?
 
 pub struct ImportedGltf {
     pub default_scene_id: Option<Uuid>,
+    // 화면에 그리고 Node Tree 에 보여주는 scene. 처음에는 default scene 이다
+    pub active_scene_id: Option<Uuid>,
     pub scenes: HashMap<Uuid, Scene>,
     pub nodes: HashMap<Uuid, Node>,
     pub meshes: HashMap<Uuid, Mesh>,
     pub materials: HashMap<Uuid, Material>,
+    pub images: HashMap<Uuid, Image>,
+    pub samplers: HashMap<Uuid, Sampler>,
 }
 
 impl ImportedGltf {
-    pub fn default_scene(&self) -> &Scene {
+    // scene 이 하나도 없는 glTF 도 있다
+    pub fn default_scene(&self) -> Option<&Scene> {
         if let Some(default_scene_id) = self.default_scene_id {
-            &self.scenes[&default_scene_id]
+            Some(&self.scenes[&default_scene_id])
         } else {
-            self.scenes.iter().nth(0).map(|(_id, s)| s).unwrap()
+            self.scenes.values().next()
         }
     }
+
+    pub fn active_scene(&self) -> Option<&Scene> {
+        self.active_scene_id.map(|id| &self.scenes[&id])
+    }
+
+    // glTF 에 정의된 순서대로
+    pub fn scenes_in_order(&self) -> Vec<&Scene> {
+        let mut scenes: Vec<&Scene> = self.scenes.values().collect();
+        scenes.sort_by_key(|scene| scene.gltf_index());
+        scenes
+    }
+
+    // active scene 에서 화면에 그려지는 node 와 그 world transform
+    // scale 이 0 인 node 는 자식까지 보이지 않으므로 뺀다
+    pub fn visible_nodes(&self) -> Vec<(&Node, Matrix4<f32>)> {
+        let mut visible_nodes = Vec::new();
+        let Some(scene) = self.active_scene() else {
+            return visible_nodes;
+        };
+        let mut node_stack: Vec<(&Node, Matrix4<f32>)> = scene
+            .nodes
+            .iter()
+            .map(|root_node_id| (&self.nodes[root_node_id], Matrix4::identity()))
+            .collect();
+        while let Some((node, upper_transform)) = node_stack.pop() {
+            if node.transform.scale.product() == 0.0 {
+                continue;
+            }
+            let transform = upper_transform * node.transform.matrix();
+            visible_nodes.push((node, transform));
+
+            // visit children
+            for child_id in &node.children {
+                node_stack.push((&self.nodes[child_id], transform));
+            }
+        }
+        visible_nodes
+    }
 }
 
 #[derive(Debug)]
@@ -53,6 +98,9 @@ pub enum SceneSourceInfo {
 #[derive(Debug)]
 pub struct Scene {
     pub id: Uuid,
+    pub name: Option<String>,
+    // JSON 원문
+    pub extras: Option<String>,
     pub nodes: Vec<Uuid>,
     pub source_info: SceneSourceInfo,
 }
@@ -64,6 +112,14 @@ impl Scene {
         };
         index
     }
+
+    // 이름이 없으면 glTF index 로 대신한다
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("Scene {}", self.gltf_index()),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -88,16 +144,104 @@ impl NodeTransform {
     }
 }
 
+// Euler 각을 적용하는 축 순서. 고정된 축을 기준으로 앞의 축부터 돌린다
+// 예) Xyz 는 X, Y, Z 순서로 돌리므로 R = Rz * Ry * Rx 이다 (Blender 의 XYZ Euler 와 같음)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum EulerOrder {
+    #[default]
+    Xyz,
+    Xzy,
+    Yxz,
+    Yzx,
+    Zxy,
+    Zyx,
+}
+
+impl EulerOrder {
+    pub const ALL: [EulerOrder; 6] = [
+        EulerOrder::Xyz,
+        EulerOrder::Xzy,
+        EulerOrder::Yxz,
+        EulerOrder::Yzx,
+        EulerOrder::Zxy,
+        EulerOrder::Zyx,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            EulerOrder::Xyz => "XYZ",
+            EulerOrder::Xzy => "XZY",
+            EulerOrder::Yxz => "YXZ",
+            EulerOrder::Yzx => "YZX",
+            EulerOrder::Zxy => "ZXY",
+            EulerOrder::Zyx => "ZYX",
+        }
+    }
+
+    // 적용하는 순서대로의 축 번호 (x = 0, y = 1, z = 2)
+    fn axes(self) -> [usize; 3] {
+        match self {
+            EulerOrder::Xyz => [0, 1, 2],
+            EulerOrder::Xzy => [0, 2, 1],
+            EulerOrder::Yxz => [1, 0, 2],
+            EulerOrder::Yzx => [1, 2, 0],
+            EulerOrder::Zxy => [2, 0, 1],
+            EulerOrder::Zyx => [2, 1, 0],
+        }
+    }
+
+    // XYZ 를 순환시킨 순서면 1, 아니면 -1
+    fn parity(self) -> f32 {
+        match self {
+            EulerOrder::Xyz | EulerOrder::Yzx | EulerOrder::Zxy => 1.0,
+            EulerOrder::Xzy | EulerOrder::Yxz | EulerOrder::Zyx => -1.0,
+        }
+    }
+
+    pub fn to_quaternion(self, angles: Euler<Rad<f32>>) -> Quaternion<f32> {
+        let angles = [angles.x, angles.y, angles.z];
+        let axis_rotation = |axis: usize| {
+            let mut unit = Vector3::zero();
+            unit[axis] = 1.0;
+            Quaternion::from_axis_angle(unit, angles[axis])
+        };
+        let [i, j, k] = self.axes();
+        axis_rotation(k) * axis_rotation(j) * axis_rotation(i)
+    }
+
+    // 두 번째 축의 각은 -90° ~ 90° 로 나온다
+    // 두 번째 축이 ±90° 인 gimbal lock 상태에서는 첫 번째와 세 번째 축이 같은 회전을 하므로 세 번째 축을 0 으로 둔다
+    pub fn to_euler(self, rotation: Quaternion<f32>) -> Euler<Rad<f32>> {
+        let m = Matrix3::from(rotation.normalize());
+        // cgmath 는 column major 이므로 행, 열 순서로 읽는 helper
+        let r = |row: usize, col: usize| m[col][row];
+        let [i, j, k] = self.axes();
+        let parity = self.parity();
+
+        let mut angles = [Rad(0.0); 3];
+        // ±90° 근처에서는 asin 의 오차가 크므로 atan2 로 구한다
+        let cos_second = (r(i, i) * r(i, i) + r(j, i) * r(j, i)).sqrt();
+        angles[j] = Rad::atan2(-parity * r(k, i), cos_second);
+        if cos_second > 1e-4 {
+            angles[i] = Rad::atan2(parity * r(k, j), r(k, k));
+            angles[k] = Rad::atan2(parity * r(j, i), r(i, i));
+        } else {
+            angles[i] = Rad::atan2(-parity * r(j, k), r(j, j));
+        }
+        Euler::new(angles[0], angles[1], angles[2])
+    }
+}
+
 #[derive(Debug)]
 pub struct Node {
     pub id: Uuid,
+    pub name: Option<String>,
+    // JSON 원문
+    pub extras: Option<String>,
     pub transform: NodeTransform,
     pub children: Vec<Uuid>,
     pub mesh_id: Option<Uuid>,
 
-    pub uniform_buffer: wgpu::Buffer,
-    pub uniform_bind_group: wgpu::BindGroup,
-
     pub source_info: NodeSourceInfo,
 }
 
@@ -112,6 +256,14 @@ impl Node {
     pub fn abbreviated_id(&self) -> String {
         self.id.to_string()[..8].to_string()
     }
+
+    // 이름이 없으면 glTF index 로 대신한다
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("Node {}", self.gltf_index()),
+        }
+    }
 }
 
 pub enum MaterialSourceInfo {
@@ -121,11 +273,23 @@ pub enum MaterialSourceInfo {
 
 pub struct Material {
     pub id: Uuid,
+    pub name: Option<String>,
+    // JSON 원문
+    pub extras: Option<String>,
     pub base_color_factor: Vector4<f32>,
     pub emissive_factor: Vector3<f32>,
-
-    pub uniform_buffer: wgpu::Buffer,
-    pub material_bind_group: wgpu::BindGroup,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    pub base_color_texture: Option<TextureRef>,
+    pub metallic_roughness_texture: Option<TextureRef>,
+    pub normal_texture: Option<TextureRef>,
+    pub occlusion_texture: Option<TextureRef>,
+    pub emissive_texture: Option<TextureRef>,
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
 
     pub source_info: MaterialSourceInfo,
     // TODO: enum
@@ -138,4 +302,135 @@ impl Material {
         };
         index
     }
+
+    // 이름이 없으면 glTF index 로 대신한다
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("Material {}", self.gltf_index()),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum AlphaMode {
+    Opaque,
+    Mask,
+    Blend,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TextureRef {
+    pub image_id: Uuid,
+    pub sampler_id: Uuid,
+    pub tex_coord: u32,
+}
+
+pub enum ImageSourceInfo {
+    Gltf { index: usize },
+    SomethingElse,
+}
+
+// sRGB 여부는 image 가 아니라 참조하는 material texture 의 종류에 따라 정해진다
+pub struct Image {
+    pub id: Uuid,
+    // 0 번이 원본 크기. 나머지는 미리 만들어 둔 mipmap
+    pub mips: Vec<image::RgbaImage>,
+    pub source_info: ImageSourceInfo,
+}
+
+impl Image {
+    pub fn gltf_index(&self) -> usize {
+        let ImageSourceInfo::Gltf { index } = self.source_info else {
+            panic!("Source is not glTF");
+        };
+        index
+    }
+}
+
+pub enum SamplerSourceInfo {
+    Gltf { index: usize },
+    SomethingElse,
+}
+
+pub struct Sampler {
+    pub id: Uuid,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    // None 이면 mipmap 을 쓰지 않고 원본만 읽는다
+    pub mipmap_filter: Option<wgpu::FilterMode>,
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub source_info: SamplerSourceInfo,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // q 와 -q 는 같은 회전이다
+    fn assert_same_rotation(a: Quaternion<f32>, b: Quaternion<f32>) {
+        let dot = a.dot(b).abs();
+        assert!((dot - 1.0).abs() < 1e-6, "{:?} != {:?}", a, b);
+    }
+
+    fn euler(order: EulerOrder, first: f32, second: f32, third: f32) -> Euler<Rad<f32>> {
+        let mut angles = [Rad(0.0); 3];
+        let [i, j, k] = order.axes();
+        angles[i] = Deg(first).into();
+        angles[j] = Deg(second).into();
+        angles[k] = Deg(third).into();
+        Euler::new(angles[0], angles[1], angles[2])
+    }
+
+    #[test]
+    fn euler_round_trip() {
+        let cases = [
+            (0.0, 0.0, 0.0),
+            (30.0, 45.0, 60.0),
+            (-170.0, -80.0, 120.0),
+            (10.0, 89.9, -20.0),
+            (135.0, -89.9, 45.0),
+        ];
+        for order in EulerOrder::ALL {
+            for (first, second, third) in cases {
+                let angles = euler(order, first, second, third);
+                let rotation = order.to_quaternion(angles);
+                let converted = order.to_euler(rotation);
+                assert_same_rotation(order.to_quaternion(converted), rotation);
+                // 범위 안의 각이면 그대로 돌아온다
+                for (a, b) in [
+                    (angles.x, converted.x),
+                    (angles.y, converted.y),
+                    (angles.z, converted.z),
+                ] {
+                    assert!(
+                        (a - b).0.abs() < 1e-3,
+                        "{:?} {:?} != {:?}",
+                        order,
+                        angles,
+                        converted
+                    );
+                }
+            }
+        }
+    }
+
+    // 두 번째 축이 ±90° 이면 각은 달라질 수 있지만 회전은 같아야 한다
+    #[test]
+    fn euler_round_trip_in_gimbal_lock() {
+        for order in EulerOrder::ALL {
+            for second in [90.0, -90.0] {
+                for (first, third) in [(0.0, 0.0), (30.0, 40.0), (-120.0, 75.0)] {
+                    let rotation = order.to_quaternion(euler(order, first, second, third));
+                    let converted = order.to_euler(rotation);
+                    assert_same_rotation(order.to_quaternion(converted), rotation);
+                    let [_, j, k] = order.axes();
+                    let converted = [converted.x, converted.y, converted.z];
+                    assert!((converted[j] - Rad::from(Deg(second))).0.abs() < 1e-3);
+                    assert_eq!(converted[k], Rad(0.0));
+                }
+            }
+        }
+    }
 }
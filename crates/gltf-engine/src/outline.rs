@@ -0,0 +1,256 @@
+use crate::pipeline::{preprocess, PipelineKey};
+use crate::resource_cache::{NodeResource, PrimitiveResource};
+use std::collections::HashMap;
+
+// 선택된 node 의 외곽선을 그린다 (Engine::set_selection)
+// 1. 선택된 primitive 를 depth test 없이 mask texture 에 그린다. 그래서 가려진 부분도 외곽선이 보인다
+// 2. 화면 전체를 돌면서 mask 의 바깥쪽 경계를 color target 에 칠한다 (edge detection)
+// normal 을 따라 부풀리는 방식과 달리 normal 이 없는 primitive, 점, 선에도 외곽선이 생긴다
+
+const SHADER_SOURCE: &str = include_str!("outline.wgsl");
+const MASK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+const POSITION_ATTRIBUTES: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![0 => Float32x3];
+
+// mask 는 position 만 쓰므로 topology 가 같으면 pipeline 을 같이 쓴다
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+struct MaskPipelineKey {
+    topology: wgpu::PrimitiveTopology,
+    strip_index_format: Option<wgpu::IndexFormat>,
+}
+
+pub struct OutlineRenderer {
+    mask_shader: wgpu::ShaderModule,
+    mask_pipeline_layout: wgpu::PipelineLayout,
+    mask_pipelines: HashMap<MaskPipelineKey, wgpu::RenderPipeline>,
+    // view 가 참조하므로 함께 가지고 있는다
+    #[allow(dead_code)]
+    mask_texture: wgpu::Texture,
+    mask_view: wgpu::TextureView,
+    outline_bind_group_layout: wgpu::BindGroupLayout,
+    outline_bind_group: wgpu::BindGroup,
+    outline_pipeline: wgpu::RenderPipeline,
+}
+
+impl OutlineRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        node_layout: &wgpu::BindGroupLayout,
+        target_format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let mask_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("outline.wgsl MASK"),
+            source: wgpu::ShaderSource::Wgsl(preprocess(SHADER_SOURCE, &["MASK"]).into()),
+        });
+        let mask_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Outline Mask Pipeline Layout"),
+            bind_group_layouts: &[camera_layout, node_layout],
+            push_constant_ranges: &[],
+        });
+
+        let outline_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                }],
+                label: Some("outline_bind_group_layout"),
+            });
+        let outline_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("outline.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(preprocess(SHADER_SOURCE, &[]).into()),
+        });
+        let outline_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Outline Pipeline Layout"),
+                bind_group_layouts: &[&outline_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let outline_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Outline Pipeline"),
+            layout: Some(&outline_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &outline_shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &outline_shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let (mask_texture, mask_view) = create_mask_texture(device, width, height);
+        let outline_bind_group =
+            create_outline_bind_group(device, &outline_bind_group_layout, &mask_view);
+
+        Self {
+            mask_shader,
+            mask_pipeline_layout,
+            mask_pipelines: HashMap::new(),
+            mask_texture,
+            mask_view,
+            outline_bind_group_layout,
+            outline_bind_group,
+            outline_pipeline,
+        }
+    }
+
+    // color target 과 크기가 같아야 한다
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        (self.mask_texture, self.mask_view) = create_mask_texture(device, width, height);
+        self.outline_bind_group =
+            create_outline_bind_group(device, &self.outline_bind_group_layout, &self.mask_view);
+    }
+
+    // render 하기 전에, 그릴 primitive 마다 호출한다
+    pub fn prepare(&mut self, device: &wgpu::Device, key: &PipelineKey) {
+        let mask_key = MaskPipelineKey {
+            topology: key.topology,
+            strip_index_format: key.strip_index_format,
+        };
+        if self.mask_pipelines.contains_key(&mask_key) {
+            return;
+        }
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&format!("Outline Mask Pipeline {:?}", mask_key)),
+            layout: Some(&self.mask_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.mask_shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: 12,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &POSITION_ATTRIBUTES,
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.mask_shader,
+                entry_point: "fs_main",
+                targets: &[Some(MASK_FORMAT.into())],
+            }),
+            // 뒷면만 보이는 부분도 외곽선에 포함되도록 cull 하지 않는다
+            primitive: wgpu::PrimitiveState {
+                topology: mask_key.topology,
+                strip_index_format: mask_key.strip_index_format,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        self.mask_pipelines.insert(mask_key, pipeline);
+    }
+
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        color_view: &wgpu::TextureView,
+        camera_bind_group: &wgpu::BindGroup,
+        items: &[(&NodeResource, &PrimitiveResource)],
+    ) {
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Outline Mask Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.mask_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_bind_group(0, camera_bind_group, &[]);
+            for (node, primitive) in items {
+                let key = &primitive.pipeline_key;
+                render_pass.set_pipeline(
+                    &self.mask_pipelines[&MaskPipelineKey {
+                        topology: key.topology,
+                        strip_index_format: key.strip_index_format,
+                    }],
+                );
+                render_pass.set_bind_group(1, &node.uniform_bind_group, &[]);
+                // position 은 항상 첫 번째 vertex buffer 이다 (VertexAttributes::buffer_layouts)
+                render_pass.set_vertex_buffer(0, primitive.vertex_buffers[0].slice(..));
+                if let Some(indices) = &primitive.indices {
+                    render_pass.set_index_buffer(indices.buffer.slice(..), indices.format);
+                    render_pass.draw_indexed(0..(indices.count as u32), 0, 0..1);
+                } else {
+                    render_pass.draw(0..(primitive.num_vertices as u32), 0..1);
+                }
+            }
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Outline Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.outline_pipeline);
+        render_pass.set_bind_group(0, &self.outline_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+fn create_mask_texture(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("outline mask"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: MASK_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
+
+fn create_outline_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    mask_view: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::TextureView(mask_view),
+        }],
+        label: Some("outline_bind_group"),
+    })
+}
@@ -0,0 +1,68 @@
+// 선택된 node 의 외곽선 (outline.rs)
+// NOTE: MASK 를 define 하면 mask pass, 아니면 외곽선 pass 의 shader 가 된다 (pipeline.rs 의 preprocess)
+
+#ifdef MASK
+// 1. 선택된 primitive 가 덮는 pixel 을 mask 에 1 로 칠한다
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view_front: vec4<f32>,
+    view_proj: mat4x4<f32>,
+}
+
+struct Node {
+    model_mat: mat4x4<f32>,
+    normal_mat: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(1) @binding(0)
+var<uniform> node_uniform: Node;
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return camera.view_proj * node_uniform.model_mat * vec4<f32>(position, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0);
+}
+#else
+// 2. mask 바깥이면서 몇 pixel 안에 mask 가 있는 pixel 을 외곽선 색으로 칠한다
+
+@group(0) @binding(0)
+var t_mask: texture_2d<f32>;
+
+// 화면 전체를 덮는 삼각형 하나
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let outline_width = 2;
+    let center = vec2<i32>(position.xy);
+    if (textureLoad(t_mask, center, 0).r > 0.5) {
+        discard;
+    }
+    let max_coord = vec2<i32>(textureDimensions(t_mask)) - vec2<i32>(1);
+    var covered = false;
+    for (var y = -outline_width; y <= outline_width; y = y + 1) {
+        for (var x = -outline_width; x <= outline_width; x = x + 1) {
+            let coord = clamp(center + vec2<i32>(x, y), vec2<i32>(0), max_coord);
+            if (textureLoad(t_mask, coord, 0).r > 0.5) {
+                covered = true;
+            }
+        }
+    }
+    if (!covered) {
+        discard;
+    }
+    return vec4<f32>(1.0, 0.55, 0.1, 1.0);
+}
+#endif
@@ -0,0 +1,487 @@
+use crate::mesh::{Aabb, MeshPrimitive};
+use crate::model::{AlphaMode, ImportedGltf, Material};
+use cgmath::*;
+use uuid::Uuid;
+
+// viewport 에서 클릭한 위치에 보이는 node 를 CPU 에서 찾는다
+// GPU 없이 model 과 camera 행렬만 있으면 되므로 화면 없이도 쓸 수 있다
+
+#[derive(Debug, Copy, Clone)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    // 단위 벡터
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    // x, y 는 viewport 안의 위치 (왼쪽 위가 (0, 0), 오른쪽 아래가 (1, 1))
+    // view_proj 의 역행렬이 없으면 None
+    pub fn from_viewport(view_proj: Matrix4<f32>, x: f32, y: f32) -> Option<Self> {
+        let inverse = view_proj.invert()?;
+        let ndc_x = x * 2.0 - 1.0;
+        let ndc_y = 1.0 - y * 2.0;
+        // wgpu 의 NDC depth 는 0 (near) ~ 1 (far)
+        let near = inverse.transform_point(Point3::new(ndc_x, ndc_y, 0.0));
+        let far = inverse.transform_point(Point3::new(ndc_x, ndc_y, 1.0));
+        Some(Self {
+            origin: near,
+            direction: (far - near).normalize(),
+        })
+    }
+
+    fn transform(&self, transform: Matrix4<f32>) -> Self {
+        Self {
+            origin: transform.transform_point(self.origin),
+            direction: transform.transform_vector(self.direction),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct PickHit {
+    pub node_id: Uuid,
+    pub primitive_id: Uuid,
+    // ray 의 origin 으로부터의 거리 (world 좌표계)
+    pub distance: f32,
+}
+
+// active scene 에서 ray 와 가장 가까이서 만나는 primitive
+// 렌더러에서 보이지 않는 부분 (cull 되는 뒷면, alpha mask 로 버려지거나 완전히 투명한 부분) 은 맞지 않는다
+pub fn pick(model_root: &ImportedGltf, ray: &Ray) -> Option<PickHit> {
+    let mut closest: Option<PickHit> = None;
+    for (node, transform) in model_root.visible_nodes() {
+        let Some(mesh_id) = node.mesh_id else {
+            continue;
+        };
+        let Some(inverse) = transform.invert() else {
+            continue;
+        };
+        // model 좌표계로 옮긴 ray. direction 을 정규화하지 않았으므로 t 는 world 좌표계의 거리 그대로다
+        let local_ray = ray.transform(inverse);
+        // 좌우가 뒤집힌 transform 은 화면에서 winding 도 뒤집는다
+        let mirrored = transform.determinant() < 0.0;
+        let primitives = model_root.meshes[&mesh_id].primitives.iter().flatten();
+        for primitive in primitives {
+            let max_distance = closest.map_or(f32::INFINITY, |hit| hit.distance);
+            if intersect_aabb(&local_ray, &primitive.bounds).map_or(true, |t| t > max_distance) {
+                continue;
+            }
+            // material 이 없으면 glTF 의 기본 material (opaque, 한쪽 면) 로 그린다
+            let material = primitive.material_id.map(|id| &model_root.materials[&id]);
+            let double_sided = material.map_or(false, |m| m.double_sided);
+            let sides = match (double_sided, mirrored) {
+                (true, _) => Sides::Both,
+                (false, false) => Sides::CounterClockwise,
+                (false, true) => Sides::Clockwise,
+            };
+            let is_visible = |vertices: [usize; 3], u: f32, v: f32| {
+                is_visible(model_root, primitive, material, vertices, u, v)
+            };
+            if let Some(distance) =
+                intersect_primitive(&local_ray, primitive, sides, max_distance, is_visible)
+            {
+                closest = Some(PickHit {
+                    node_id: node.id,
+                    primitive_id: primitive.id,
+                    distance,
+                });
+            }
+        }
+    }
+    closest
+}
+
+// ray 가 맞을 수 있는 면. ray 쪽에서 봤을 때 vertex 가 반시계 방향이면 CounterClockwise
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Sides {
+    Both,
+    CounterClockwise,
+    Clockwise,
+}
+
+// slab method. 만나면 상자에 들어가는 지점까지의 거리 (ray 가 상자 안에서 시작하면 0)
+fn intersect_aabb(ray: &Ray, aabb: &Aabb) -> Option<f32> {
+    let mut t_min = 0.0f32;
+    let mut t_max = f32::INFINITY;
+    for i in 0..3 {
+        let inverse_direction = 1.0 / ray.direction[i];
+        let t0 = (aabb.min[i] - ray.origin[i]) * inverse_direction;
+        let t1 = (aabb.max[i] - ray.origin[i]) * inverse_direction;
+        // 축과 평행한 ray 는 inf, NaN 이 나오는데 min, max 가 NaN 을 무시한다
+        t_min = t_min.max(t0.min(t1));
+        t_max = t_max.min(t0.max(t1));
+    }
+    (t_min <= t_max).then_some(t_min)
+}
+
+// max_distance 보다 가까운 삼각형 중 가장 가까운 것까지의 거리
+// is_visible 은 삼각형의 vertex index 와 맞은 지점의 barycentric 좌표 (u, v) 를 받는다
+// 점, 선 primitive 는 두께가 없으므로 맞지 않는다
+fn intersect_primitive(
+    ray: &Ray,
+    primitive: &MeshPrimitive,
+    sides: Sides,
+    max_distance: f32,
+    is_visible: impl Fn([usize; 3], f32, f32) -> bool,
+) -> Option<f32> {
+    use wgpu::PrimitiveTopology as T;
+
+    let vertex_count = primitive
+        .indices
+        .as_ref()
+        .map_or(primitive.positions.len(), |indices| indices.len());
+    let vertex = |i: usize| match &primitive.indices {
+        Some(indices) => indices.get(i) as usize,
+        None => i,
+    };
+    let (triangle_count, stride) = match primitive.topology {
+        T::TriangleList => (vertex_count / 3, 3),
+        T::TriangleStrip => (vertex_count.saturating_sub(2), 1),
+        T::PointList | T::LineList | T::LineStrip => return None,
+    };
+
+    let mut closest: Option<f32> = None;
+    for triangle in 0..triangle_count {
+        let first = triangle * stride;
+        // strip 의 홀수 번째 삼각형은 winding 이 반대이므로 순서를 바꿔준다
+        let order = if stride == 1 && triangle % 2 == 1 {
+            [1, 0, 2]
+        } else {
+            [0, 1, 2]
+        };
+        let vertices = order.map(|i| vertex(first + i));
+        let [a, b, c] = vertices.map(|i| Point3::from(primitive.positions[i]));
+        if let Some((t, u, v)) = intersect_triangle(ray, a, b, c, sides) {
+            if t < closest.unwrap_or(max_distance) && is_visible(vertices, u, v) {
+                closest = Some(t);
+            }
+        }
+    }
+    closest
+}
+
+// Möller–Trumbore. 만나면 거리 t 와 barycentric 좌표 (u, v) (만난 점은 a + u * (b - a) + v * (c - a))
+fn intersect_triangle(
+    ray: &Ray,
+    a: Point3<f32>,
+    b: Point3<f32>,
+    c: Point3<f32>,
+    sides: Sides,
+) -> Option<(f32, f32, f32)> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = ray.direction.cross(edge2);
+    // ray 쪽에서 봤을 때 반시계 방향이면 양수
+    let determinant = edge1.dot(p);
+    // ray 가 삼각형과 평행하거나 넓이가 0 인 삼각형
+    if determinant.abs() < f32::EPSILON * edge1.magnitude() * edge2.magnitude() {
+        return None;
+    }
+    match sides {
+        Sides::CounterClockwise if determinant < 0.0 => return None,
+        Sides::Clockwise if determinant > 0.0 => return None,
+        _ => {}
+    }
+    let inverse_determinant = 1.0 / determinant;
+    let s = ray.origin - a;
+    let u = s.dot(p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(edge1);
+    let v = ray.direction.dot(q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(q) * inverse_determinant;
+    (t >= 0.0).then_some((t, u, v))
+}
+
+// shader 와 같은 방법으로 맞은 지점의 alpha 를 계산해서 화면에 보이는지 확인한다
+// texture 는 mipmap 없이 원본에서 가장 가까운 texel 을 읽는다
+fn is_visible(
+    model_root: &ImportedGltf,
+    primitive: &MeshPrimitive,
+    material: Option<&Material>,
+    vertices: [usize; 3],
+    u: f32,
+    v: f32,
+) -> bool {
+    let Some(material) = material.filter(|m| m.alpha_mode != AlphaMode::Opaque) else {
+        return true;
+    };
+    let weights = [1.0 - u - v, u, v];
+    let interpolate = |values: &[[f32; 2]]| {
+        (0..3).fold(Vector2::zero(), |sum, i| {
+            sum + Vector2::from(values[vertices[i]]) * weights[i]
+        })
+    };
+
+    let mut alpha = material.base_color_factor.w;
+    if let Some(colors) = &primitive.colors {
+        alpha *= (0..3)
+            .map(|i| colors[vertices[i]][3] * weights[i])
+            .sum::<f32>();
+    }
+    if let Some(texture) = &material.base_color_texture {
+        let tex_coords = match texture.tex_coord {
+            1 => &primitive.tex_coords_1,
+            _ => &primitive.tex_coords,
+        };
+        // shader 에서도 texcoord 가 없는 primitive 는 (0, 0) 을 읽는다
+        let uv = tex_coords.as_deref().map_or(Vector2::zero(), interpolate);
+        let sampler = &model_root.samplers[&texture.sampler_id];
+        let image = &model_root.images[&texture.image_id].mips[0];
+        let x = texel(uv.x, sampler.address_mode_u, image.width());
+        let y = texel(uv.y, sampler.address_mode_v, image.height());
+        alpha *= image.get_pixel(x, y)[3] as f32 / 255.0;
+    }
+
+    match material.alpha_mode {
+        AlphaMode::Opaque => true,
+        AlphaMode::Mask => alpha >= material.alpha_cutoff,
+        AlphaMode::Blend => alpha > 0.0,
+    }
+}
+
+fn texel(coord: f32, address_mode: wgpu::AddressMode, size: u32) -> u32 {
+    let coord = match address_mode {
+        wgpu::AddressMode::Repeat => coord.rem_euclid(1.0),
+        wgpu::AddressMode::MirrorRepeat => {
+            let coord = coord.rem_euclid(2.0);
+            if coord > 1.0 {
+                2.0 - coord
+            } else {
+                coord
+            }
+        }
+        _ => coord.clamp(0.0, 1.0),
+    };
+    ((coord * size as f32) as u32).min(size.saturating_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::{Camera, Projection};
+    use crate::import::ImportOptions;
+    use crate::mesh::PrimitiveSourceInfo;
+    use crate::test_util;
+    use wgpu::PrimitiveTopology as T;
+
+    fn ray(origin: [f32; 3], direction: [f32; 3]) -> Ray {
+        Ray {
+            origin: origin.into(),
+            direction: Vector3::from(direction).normalize(),
+        }
+    }
+
+    fn primitive(topology: wgpu::PrimitiveTopology, positions: Vec<[f32; 3]>) -> MeshPrimitive {
+        MeshPrimitive {
+            id: Uuid::new_v4(),
+            topology,
+            bounds: Aabb::from_points(&positions),
+            positions,
+            normals: None,
+            tex_coords: None,
+            tex_coords_1: None,
+            tangents: None,
+            colors: None,
+            joints: None,
+            weights: None,
+            indices: None,
+            material_id: None,
+            source_info: PrimitiveSourceInfo::Gltf { index: 0 },
+        }
+    }
+
+    const UNIT_SQUARE: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0],
+        [1.0, 1.0, 0.0],
+    ];
+
+    fn hit(ray: &Ray, primitive: &MeshPrimitive, sides: Sides, max_distance: f32) -> Option<f32> {
+        intersect_primitive(ray, primitive, sides, max_distance, |_, _, _| true)
+    }
+
+    #[test]
+    fn ray_hits_and_misses_triangle() {
+        let triangle = primitive(T::TriangleList, UNIT_SQUARE[..3].to_vec());
+        let front = ray([0.25, 0.25, 5.0], [0.0, 0.0, -1.0]);
+        let back = ray([0.25, 0.25, -2.0], [0.0, 0.0, 1.0]);
+        let distance = hit(&front, &triangle, Sides::Both, 100.0);
+        assert!((distance.unwrap() - 5.0).abs() < 1e-5);
+        let distance = hit(&back, &triangle, Sides::Both, 100.0);
+        assert!((distance.unwrap() - 2.0).abs() < 1e-5);
+
+        // 한쪽 면만 맞는다
+        assert!(hit(&front, &triangle, Sides::CounterClockwise, 100.0).is_some());
+        assert!(hit(&back, &triangle, Sides::CounterClockwise, 100.0).is_none());
+        assert!(hit(&front, &triangle, Sides::Clockwise, 100.0).is_none());
+        assert!(hit(&back, &triangle, Sides::Clockwise, 100.0).is_some());
+
+        // 삼각형 바깥, 반대 방향, max_distance 보다 먼 경우
+        let miss = [
+            (ray([0.75, 0.75, 5.0], [0.0, 0.0, -1.0]), 100.0),
+            (ray([0.25, 0.25, 5.0], [0.0, 0.0, 1.0]), 100.0),
+            (ray([0.25, 0.25, 5.0], [0.0, 0.0, -1.0]), 4.0),
+        ];
+        for (ray, max_distance) in miss {
+            assert!(hit(&ray, &triangle, Sides::Both, max_distance).is_none());
+        }
+    }
+
+    #[test]
+    fn triangle_strip_covers_every_triangle() {
+        let strip = primitive(T::TriangleStrip, UNIT_SQUARE.to_vec());
+        // 첫 번째 삼각형 (0, 1, 2) 과 winding 을 바로잡은 두 번째 삼각형 (2, 1, 3)
+        for [x, y] in [[0.25, 0.25], [0.75, 0.75]] {
+            let ray = ray([x, y, 1.0], [0.0, 0.0, -1.0]);
+            let distance = hit(&ray, &strip, Sides::CounterClockwise, 100.0);
+            assert!(distance.is_some(), "{} {}", x, y);
+        }
+
+        // 두께가 없는 primitive 는 맞지 않는다
+        for topology in [T::PointList, T::LineList, T::LineStrip] {
+            let primitive = primitive(topology, UNIT_SQUARE.to_vec());
+            let ray = ray([0.25, 0.25, 1.0], [0.0, 0.0, -1.0]);
+            assert!(hit(&ray, &primitive, Sides::Both, 100.0).is_none());
+        }
+    }
+
+    const OVERLAPPING_NODES: &str = r#"[
+        { "name": "Near", "mesh": 0 },
+        { "name": "Far", "mesh": 0, "translation": [0, 0, -2] }
+    ]"#;
+
+    // TRIANGLE_FAN mesh 를 쓰는 node 두 개. 앞면이 +z 방향을 본다
+    fn fans(material: &str, nodes: &str) -> ImportedGltf {
+        let bin =
+            test_util::f32_bytes(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0]);
+        let json = format!(
+            r#"{{
+                "bufferViews": [{{ "buffer": 0, "byteLength": 48 }}],
+                "accessors": [
+                    {{ "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3",
+                       "min": [0, 0, 0], "max": [1, 1, 0] }}
+                ],
+                "materials": [{}],
+                "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "material": 0, "mode": 6 }}] }}],
+                "nodes": {},
+                "scenes": [{{ "nodes": [0, 1] }}]
+            }}"#,
+            material, nodes
+        );
+        test_util::prepare(&json, &bin, &ImportOptions::default())
+    }
+
+    fn hit_name(model_root: &ImportedGltf, hit: Option<PickHit>) -> Option<&str> {
+        model_root.nodes[&hit?.node_id].name.as_deref()
+    }
+
+    #[test]
+    fn pick_returns_nearest_hit() {
+        let model_root = fans(r#"{ "doubleSided": true }"#, OVERLAPPING_NODES);
+
+        let hit = pick(&model_root, &ray([0.5, 0.25, 5.0], [0.0, 0.0, -1.0]));
+        assert_eq!(hit_name(&model_root, hit), Some("Near"));
+        assert!((hit.unwrap().distance - 5.0).abs() < 1e-5);
+
+        let hit = pick(&model_root, &ray([0.25, 0.75, -5.0], [0.0, 0.0, 1.0]));
+        assert_eq!(hit_name(&model_root, hit), Some("Far"));
+        assert!((hit.unwrap().distance - 3.0).abs() < 1e-5);
+
+        assert!(pick(&model_root, &ray([2.0, 2.0, 5.0], [0.0, 0.0, -1.0])).is_none());
+    }
+
+    #[test]
+    fn single_sided_material_is_hit_only_from_front() {
+        let from_front = ray([0.5, 0.25, 5.0], [0.0, 0.0, -1.0]);
+        let from_back = ray([0.5, 0.25, -5.0], [0.0, 0.0, 1.0]);
+
+        let model_root = fans("{}", OVERLAPPING_NODES);
+        assert_eq!(
+            hit_name(&model_root, pick(&model_root, &from_front)),
+            Some("Near")
+        );
+        assert!(pick(&model_root, &from_back).is_none());
+
+        // 뒤집힌 transform 은 winding 도 뒤집는다
+        // z 축으로 뒤집으면 vertex 가 그대로이므로 앞면도 그대로다
+        let model_root = fans(
+            "{}",
+            r#"[
+                { "name": "Near", "mesh": 0, "scale": [1, 1, -1] },
+                { "name": "Far", "mesh": 0, "translation": [0, 0, -2], "scale": [1, 1, -1] }
+            ]"#,
+        );
+        assert_eq!(
+            hit_name(&model_root, pick(&model_root, &from_front)),
+            Some("Near")
+        );
+        assert!(pick(&model_root, &from_back).is_none());
+
+        // x 축으로 뒤집으면 앞면이 -z 방향을 본다
+        let model_root = fans(
+            "{}",
+            r#"[
+                { "name": "Near", "mesh": 0, "scale": [-1, 1, 1] },
+                { "name": "Far", "mesh": 0, "translation": [0, 0, -2], "scale": [-1, 1, 1] }
+            ]"#,
+        );
+        let from_front = ray([-0.5, 0.25, 5.0], [0.0, 0.0, -1.0]);
+        let from_back = ray([-0.5, 0.25, -5.0], [0.0, 0.0, 1.0]);
+        assert!(pick(&model_root, &from_front).is_none());
+        assert_eq!(
+            hit_name(&model_root, pick(&model_root, &from_back)),
+            Some("Far")
+        );
+    }
+
+    #[test]
+    fn transparent_parts_are_not_hit() {
+        let from_front = ray([0.5, 0.25, 5.0], [0.0, 0.0, -1.0]);
+        let material = |alpha_mode: &str, alpha: f32| {
+            format!(
+                r#"{{ "alphaMode": "{}", "pbrMetallicRoughness": {{ "baseColorFactor": [1, 1, 1, {}] }} }}"#,
+                alpha_mode, alpha
+            )
+        };
+
+        // alpha cutoff 의 기본값은 0.5
+        for (alpha_mode, alpha, is_hit) in [
+            ("MASK", 0.25, false),
+            ("MASK", 0.75, true),
+            ("BLEND", 0.0, false),
+            ("BLEND", 0.25, true),
+        ] {
+            let model_root = fans(&material(alpha_mode, alpha), OVERLAPPING_NODES);
+            let hit = pick(&model_root, &from_front);
+            assert_eq!(hit.is_some(), is_hit, "{} {}", alpha_mode, alpha);
+        }
+    }
+
+    #[test]
+    fn texel_follows_address_mode() {
+        use wgpu::AddressMode as A;
+        assert_eq!(texel(1.25, A::Repeat, 4), 1);
+        assert_eq!(texel(-0.25, A::Repeat, 4), 3);
+        assert_eq!(texel(1.25, A::MirrorRepeat, 4), 3);
+        assert_eq!(texel(1.25, A::ClampToEdge, 4), 3);
+        assert_eq!(texel(-0.25, A::ClampToEdge, 4), 0);
+    }
+
+    #[test]
+    fn viewport_center_ray_follows_camera_front() {
+        let camera = Camera::new((1.0, 2.0, 3.0), Deg(-60.0), Deg(-20.0));
+        let projection = Projection::new(800, 600, Deg(45.0), 0.1, 100.0);
+        let view_proj = projection.calc_matrix() * camera.calc_matrix();
+
+        let ray = Ray::from_viewport(view_proj, 0.5, 0.5).unwrap();
+        assert!((ray.direction - camera.front()).magnitude() < 1e-3);
+        let to_origin = (ray.origin - camera.position).normalize();
+        assert!((to_origin - camera.front()).magnitude() < 1e-3);
+    }
+}
@@ -0,0 +1,343 @@
+use crate::model::{AlphaMode, Material};
+use crate::texture;
+use std::collections::HashMap;
+
+// glTF primitive 마다 vertex attribute 구성이 다를 수 있으므로, 구성 별로 pipeline 을 만들어 재사용한다
+// shader 코드는 하나이고, define 에 따라 #ifdef 로 필요한 부분만 남긴다
+
+const SHADER_SOURCE: &str = include_str!("shader.wgsl");
+
+// position 은 항상 있다고 가정한다
+#[derive(Debug, Default, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct VertexAttributes {
+    // point, line 은 normal 이 없을 수 있다 (lighting 없이 그린다)
+    pub normal: bool,
+    pub tex_coord_0: bool,
+    pub tex_coord_1: bool,
+    pub tangent: bool,
+    pub color: bool,
+    pub joints_weights: bool,
+}
+
+impl VertexAttributes {
+    // vertex buffer slot 순서와 같다. PrimitiveResource 도 이 순서대로 vertex buffer 를 binding 해야 한다
+    fn buffer_layouts(&self) -> Vec<wgpu::VertexBufferLayout<'static>> {
+        let mut layouts = vec![buffer_layout(12, &POSITION_ATTRIBUTES)];
+        if self.normal {
+            layouts.push(buffer_layout(12, &NORMAL_ATTRIBUTES));
+        }
+        if self.tex_coord_0 {
+            layouts.push(buffer_layout(8, &TEX_COORD_0_ATTRIBUTES));
+        }
+        if self.tex_coord_1 {
+            layouts.push(buffer_layout(8, &TEX_COORD_1_ATTRIBUTES));
+        }
+        if self.tangent {
+            layouts.push(buffer_layout(16, &TANGENT_ATTRIBUTES));
+        }
+        if self.color {
+            layouts.push(buffer_layout(16, &COLOR_ATTRIBUTES));
+        }
+        if self.joints_weights {
+            layouts.push(buffer_layout(8, &JOINTS_ATTRIBUTES));
+            layouts.push(buffer_layout(16, &WEIGHTS_ATTRIBUTES));
+        }
+        layouts
+    }
+
+    fn defines(&self) -> Vec<&'static str> {
+        let mut defines = Vec::new();
+        if self.normal {
+            defines.push("HAS_NORMAL");
+        }
+        if self.tex_coord_0 {
+            defines.push("HAS_TEX_COORD_0");
+        }
+        if self.tex_coord_1 {
+            defines.push("HAS_TEX_COORD_1");
+        }
+        if self.tangent {
+            defines.push("HAS_TANGENT");
+        }
+        if self.color {
+            defines.push("HAS_COLOR");
+        }
+        if self.joints_weights {
+            defines.push("HAS_JOINTS_WEIGHTS");
+        }
+        defines
+    }
+}
+
+const POSITION_ATTRIBUTES: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![0 => Float32x3];
+const NORMAL_ATTRIBUTES: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![1 => Float32x3];
+const TEX_COORD_0_ATTRIBUTES: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![2 => Float32x2];
+const TEX_COORD_1_ATTRIBUTES: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![3 => Float32x2];
+const TANGENT_ATTRIBUTES: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![4 => Float32x4];
+const COLOR_ATTRIBUTES: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![5 => Float32x4];
+const JOINTS_ATTRIBUTES: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![6 => Uint16x4];
+const WEIGHTS_ATTRIBUTES: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![7 => Float32x4];
+
+fn buffer_layout(
+    array_stride: wgpu::BufferAddress,
+    attributes: &'static [wgpu::VertexAttribute],
+) -> wgpu::VertexBufferLayout<'static> {
+    wgpu::VertexBufferLayout {
+        array_stride,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes,
+    }
+}
+
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct PipelineKey {
+    pub attributes: VertexAttributes,
+    pub topology: wgpu::PrimitiveTopology,
+    // index 가 있는 strip 을 그릴 때만 필요하다
+    pub strip_index_format: Option<wgpu::IndexFormat>,
+    pub alpha_mode: AlphaMode,
+    pub cull_mode: Option<wgpu::Face>,
+    // attribute, alpha mode 로부터 결정된다. 정렬되어 있어야 같은 shader module 을 재사용할 수 있다
+    pub defines: Vec<&'static str>,
+}
+
+impl PipelineKey {
+    pub fn new(
+        attributes: VertexAttributes,
+        topology: wgpu::PrimitiveTopology,
+        strip_index_format: Option<wgpu::IndexFormat>,
+        material: Option<&Material>,
+    ) -> Self {
+        let alpha_mode = material.map_or(AlphaMode::Opaque, |m| m.alpha_mode);
+        let double_sided = material.map_or(false, |m| m.double_sided);
+
+        let mut defines = attributes.defines();
+        match alpha_mode {
+            AlphaMode::Opaque => {}
+            AlphaMode::Mask => defines.push("ALPHA_MASK"),
+            AlphaMode::Blend => defines.push("ALPHA_BLEND"),
+        }
+        defines.sort_unstable();
+
+        // double sided material 의 뒷면은 shader 에서 normal 을 뒤집어서 shading 한다
+        let cull_mode = if double_sided || !is_triangle_topology(topology) {
+            None
+        } else {
+            Some(wgpu::Face::Back)
+        };
+
+        Self {
+            attributes,
+            topology,
+            strip_index_format: strip_index_format.filter(|_| is_strip_topology(topology)),
+            alpha_mode,
+            cull_mode,
+            defines,
+        }
+    }
+}
+
+fn is_triangle_topology(topology: wgpu::PrimitiveTopology) -> bool {
+    matches!(
+        topology,
+        wgpu::PrimitiveTopology::TriangleList | wgpu::PrimitiveTopology::TriangleStrip
+    )
+}
+
+fn is_strip_topology(topology: wgpu::PrimitiveTopology) -> bool {
+    matches!(
+        topology,
+        wgpu::PrimitiveTopology::LineStrip | wgpu::PrimitiveTopology::TriangleStrip
+    )
+}
+
+pub struct PipelineCache {
+    layout: wgpu::PipelineLayout,
+    target_format: wgpu::TextureFormat,
+    shader_modules: HashMap<Vec<&'static str>, wgpu::ShaderModule>,
+    pipelines: HashMap<PipelineKey, wgpu::RenderPipeline>,
+}
+
+impl PipelineCache {
+    pub fn new(layout: wgpu::PipelineLayout, target_format: wgpu::TextureFormat) -> Self {
+        Self {
+            layout,
+            target_format,
+            shader_modules: HashMap::new(),
+            pipelines: HashMap::new(),
+        }
+    }
+
+    // ResourceCache::sync 에서 호출해서, render 시점에는 get 으로 꺼내 쓰기만 한다
+    pub fn prepare(&mut self, device: &wgpu::Device, key: &PipelineKey) {
+        if self.pipelines.contains_key(key) {
+            return;
+        }
+
+        let shader = self
+            .shader_modules
+            .entry(key.defines.clone())
+            .or_insert_with(|| {
+                let source = preprocess(SHADER_SOURCE, &key.defines);
+                device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some(&format!("shader.wgsl {:?}", key.defines)),
+                    source: wgpu::ShaderSource::Wgsl(source.into()),
+                })
+            });
+
+        let pipeline =
+            create_render_pipeline(device, &self.layout, shader, self.target_format, key);
+        self.pipelines.insert(key.clone(), pipeline);
+    }
+
+    pub fn get(&self, key: &PipelineKey) -> &wgpu::RenderPipeline {
+        &self.pipelines[key]
+    }
+}
+
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    target_format: wgpu::TextureFormat,
+    key: &PipelineKey,
+) -> wgpu::RenderPipeline {
+    // 반투명 primitive 끼리는 서로 가리지 않도록 depth 는 test 만 하고 write 는 하지 않는다
+    let (blend, depth_write_enabled) = match key.alpha_mode {
+        AlphaMode::Opaque | AlphaMode::Mask => (wgpu::BlendState::REPLACE, true),
+        AlphaMode::Blend => (wgpu::BlendState::ALPHA_BLENDING, false),
+    };
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(&format!("Render Pipeline {:?}", key)),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &key.attributes.buffer_layouts(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: target_format,
+                blend: Some(blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: key.topology,
+            strip_index_format: key.strip_index_format,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: key.cull_mode,
+
+            polygon_mode: wgpu::PolygonMode::Fill,
+
+            unclipped_depth: false,
+
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+// WGSL 에는 preprocessor 가 없으므로, 줄 단위로 #ifdef / #ifndef / #else / #endif 만 처리한다 (중첩 가능)
+pub fn preprocess(source: &str, defines: &[&str]) -> String {
+    // 바깥 block 이 활성화 되어 있는지
+    let mut stack: Vec<bool> = Vec::new();
+    let mut active = true;
+    let mut output = String::with_capacity(source.len());
+
+    for line in source.lines() {
+        let trimmed = line.trim();
+        if let Some(name) = trimmed.strip_prefix("#ifdef ") {
+            stack.push(active);
+            active = active && defines.contains(&name.trim());
+        } else if let Some(name) = trimmed.strip_prefix("#ifndef ") {
+            stack.push(active);
+            active = active && !defines.contains(&name.trim());
+        } else if trimmed == "#else" {
+            let parent = *stack.last().expect("#else without #ifdef");
+            active = parent && !active;
+        } else if trimmed == "#endif" {
+            active = stack.pop().expect("#endif without #ifdef");
+        } else if active {
+            output.push_str(line);
+        }
+        // 줄 번호가 원본과 같도록 비활성화된 줄과 directive 도 빈 줄로 남긴다
+        output.push('\n');
+    }
+    assert!(stack.is_empty(), "Unterminated #ifdef");
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn active_lines(output: &str) -> Vec<&str> {
+        output.lines().filter(|line| !line.is_empty()).collect()
+    }
+
+    const SOURCE: &str = "\
+a
+#ifdef A
+  in_a
+#ifndef B
+  in_a_not_b
+#else
+  in_a_b
+#endif
+#else
+  not_a
+#ifdef B
+  not_a_b
+#endif
+#endif
+z";
+
+    #[test]
+    fn preprocess_handles_nested_blocks() {
+        let run = |defines: &[&str]| preprocess(SOURCE, defines);
+        assert_eq!(active_lines(&run(&[])), ["a", "  not_a", "z"]);
+        assert_eq!(
+            active_lines(&run(&["A"])),
+            ["a", "  in_a", "  in_a_not_b", "z"]
+        );
+        assert_eq!(
+            active_lines(&run(&["A", "B"])),
+            ["a", "  in_a", "  in_a_b", "z"]
+        );
+        assert_eq!(
+            active_lines(&run(&["B"])),
+            ["a", "  not_a", "  not_a_b", "z"]
+        );
+    }
+
+    // shader 의 오류 메세지에 나오는 줄 번호가 원본과 같아야 한다
+    #[test]
+    fn preprocess_keeps_line_numbers() {
+        let output = preprocess(SOURCE, &["A"]);
+        assert_eq!(output.lines().count(), SOURCE.lines().count());
+        assert_eq!(output.lines().nth(2), Some("  in_a"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Unterminated #ifdef")]
+    fn preprocess_rejects_unterminated_block() {
+        preprocess("#ifdef A\nx", &[]);
+    }
+}
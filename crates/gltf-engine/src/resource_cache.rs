@@ -0,0 +1,422 @@
+use crate::import::ImportError;
+use crate::mesh::MeshPrimitive;
+use crate::model::{ImportedGltf, Material, TextureRef};
+use crate::pipeline::{PipelineCache, PipelineKey};
+use crate::texture;
+use crate::uniform::{MaterialUniform, NodeUniform};
+use crate::MATERIAL_TEXTURE_COUNT;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+use wgpu::util::DeviceExt;
+
+// model (CPU) 에 대응하는 GPU 자원. model 의 id 를 key 로 쓴다
+// sync 를 호출하면 model 과 맞춘다
+// - 새로 생긴 id 는 자원을 만든다
+// - 사라진 id 는 자원을 버린다
+// - material 의 값, texture 가 바뀌면 uniform, bind group 을 다시 쓴다
+// - image, sampler, primitive 는 바뀌지 않는다고 가정한다 (바꾸려면 새 id 로 교체)
+// node 의 world transform 은 매 frame 계산해서 Engine::update 에서 쓴다
+
+pub struct WgpuDeps<'a> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    pub node_uniform_layout: &'a wgpu::BindGroupLayout,
+    pub material_uniform_layout: &'a wgpu::BindGroupLayout,
+    pub white_texture: &'a texture::Texture,
+}
+
+pub struct NodeResource {
+    pub uniform_buffer: wgpu::Buffer,
+    pub uniform_bind_group: wgpu::BindGroup,
+}
+
+type MaterialTextures = [Option<TextureRef>; MATERIAL_TEXTURE_COUNT as usize];
+
+pub struct MaterialResource {
+    uniform: MaterialUniform,
+    // MATERIAL_TEXTURE_COUNT 순서. 바뀌면 bind group 을 다시 만든다
+    textures: MaterialTextures,
+    uniform_buffer: wgpu::Buffer,
+    pub material_bind_group: wgpu::BindGroup,
+}
+
+pub struct PrimitiveResource {
+    // pipeline::VertexAttributes 의 vertex buffer slot 순서대로
+    pub vertex_buffers: Vec<wgpu::Buffer>,
+    pub indices: Option<PrimitiveIndices>,
+    pub num_vertices: usize,
+    // material 의 alpha mode 등이 바뀌면 달라진다
+    pub pipeline_key: PipelineKey,
+}
+
+pub struct PrimitiveIndices {
+    pub buffer: wgpu::Buffer,
+    pub format: wgpu::IndexFormat,
+    pub count: usize,
+}
+
+#[derive(Default)]
+pub struct ResourceCache {
+    pub nodes: HashMap<Uuid, NodeResource>,
+    pub materials: HashMap<Uuid, MaterialResource>,
+    // material 이 없는 primitive 에 쓴다. 처음 sync 할 때 만든다
+    pub default_material: Option<MaterialResource>,
+    pub primitives: HashMap<Uuid, PrimitiveResource>,
+    // 같은 image 를 sRGB, linear 로 모두 쓸 수 있으므로 (image id, sRGB 여부) 를 key 로 쓴다
+    textures: HashMap<(Uuid, bool), texture::Texture>,
+    samplers: HashMap<Uuid, wgpu::Sampler>,
+}
+
+impl ResourceCache {
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    // 실패하면 (device 가 지원하지 않는 크기의 texture 등) 일부 자원만 만들어진 채로 남는다
+    pub fn sync(
+        &mut self,
+        model: &ImportedGltf,
+        deps: &WgpuDeps,
+        pipeline_cache: &mut PipelineCache,
+    ) -> Result<(), ImportError> {
+        self.nodes.retain(|id, _| model.nodes.contains_key(id));
+        self.materials
+            .retain(|id, _| model.materials.contains_key(id));
+        self.textures
+            .retain(|(id, _), _| model.images.contains_key(id));
+        self.samplers
+            .retain(|id, _| model.samplers.contains_key(id));
+        let primitive_ids: HashSet<Uuid> = model
+            .meshes
+            .values()
+            .flat_map(|mesh| mesh.primitives.iter().flatten())
+            .map(|primitive| primitive.id)
+            .collect();
+        self.primitives.retain(|id, _| primitive_ids.contains(id));
+
+        for sampler in model.samplers.values() {
+            self.samplers
+                .entry(sampler.id)
+                .or_insert_with(|| create_sampler(sampler, deps));
+        }
+
+        for material in model.materials.values() {
+            let textures = material_textures(material);
+            for (texture_ref, srgb) in textures.iter().zip(TEXTURE_SRGB) {
+                if let Some(texture_ref) = texture_ref {
+                    self.prepare_texture(model, texture_ref.image_id, srgb, deps)?;
+                }
+            }
+
+            let uniform = material_uniform(material);
+            match self.materials.get_mut(&material.id) {
+                Some(resource) if resource.textures == textures => {
+                    if bytemuck::bytes_of(&resource.uniform) != bytemuck::bytes_of(&uniform) {
+                        deps.queue.write_buffer(
+                            &resource.uniform_buffer,
+                            0,
+                            bytemuck::bytes_of(&uniform),
+                        );
+                        resource.uniform = uniform;
+                    }
+                }
+                _ => {
+                    let resource = self.create_material(uniform, textures, deps);
+                    self.materials.insert(material.id, resource);
+                }
+            }
+        }
+
+        if self.default_material.is_none() {
+            let resource = self.create_material(
+                default_material_uniform(),
+                [None; MATERIAL_TEXTURE_COUNT as usize],
+                deps,
+            );
+            self.default_material = Some(resource);
+        }
+
+        for node in model.nodes.values() {
+            self.nodes
+                .entry(node.id)
+                .or_insert_with(|| create_node(deps));
+        }
+
+        for primitive in model
+            .meshes
+            .values()
+            .flat_map(|mesh| mesh.primitives.iter().flatten())
+        {
+            let material = primitive.material_id.map(|id| &model.materials[&id]);
+            let resource = self
+                .primitives
+                .entry(primitive.id)
+                .or_insert_with(|| create_primitive(primitive, material, deps));
+            resource.pipeline_key = pipeline_key(primitive, resource.indices.as_ref(), material);
+            pipeline_cache.prepare(deps.device, &resource.pipeline_key);
+        }
+
+        Ok(())
+    }
+
+    fn prepare_texture(
+        &mut self,
+        model: &ImportedGltf,
+        image_id: Uuid,
+        srgb: bool,
+        deps: &WgpuDeps,
+    ) -> Result<(), ImportError> {
+        if self.textures.contains_key(&(image_id, srgb)) {
+            return Ok(());
+        }
+        let image = &model.images[&image_id];
+        // device 가 지원하는 것보다 큰 texture 는 만들 수 없다
+        let max_dimension = deps.device.limits().max_texture_dimension_2d;
+        let (width, height) = image.mips[0].dimensions();
+        if width > max_dimension || height > max_dimension {
+            return Err(ImportError::Unsupported(format!(
+                "Image {} is {}x{}, larger than {}",
+                image.gltf_index(),
+                width,
+                height,
+                max_dimension
+            )));
+        }
+        let texture = texture::Texture::from_mips(
+            deps.device,
+            deps.queue,
+            &image.mips,
+            srgb,
+            Some("glTF Image"),
+        );
+        self.textures.insert((image_id, srgb), texture);
+        Ok(())
+    }
+
+    fn create_material(
+        &self,
+        uniform: MaterialUniform,
+        textures: MaterialTextures,
+        deps: &WgpuDeps,
+    ) -> MaterialResource {
+        let uniform_buffer = deps
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Material Uniform Buffer"),
+                contents: bytemuck::bytes_of(&uniform),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        // 없는 texture 자리는 white texture 로 채운다
+        let texture_bindings = textures
+            .iter()
+            .zip(TEXTURE_SRGB)
+            .map(|(texture_ref, srgb)| match texture_ref {
+                Some(t) => (
+                    &self.textures[&(t.image_id, srgb)].view,
+                    &self.samplers[&t.sampler_id],
+                ),
+                None => (&deps.white_texture.view, &deps.white_texture.sampler),
+            });
+
+        let mut entries = vec![wgpu::BindGroupEntry {
+            binding: 0,
+            resource: uniform_buffer.as_entire_binding(),
+        }];
+        for (i, (view, sampler)) in texture_bindings.enumerate() {
+            entries.push(wgpu::BindGroupEntry {
+                binding: 1 + i as u32 * 2,
+                resource: wgpu::BindingResource::TextureView(view),
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: 2 + i as u32 * 2,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            });
+        }
+
+        let material_bind_group = deps.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: deps.material_uniform_layout,
+            entries: &entries,
+            label: Some("material_bind_group"),
+        });
+
+        MaterialResource {
+            uniform,
+            textures,
+            uniform_buffer,
+            material_bind_group,
+        }
+    }
+}
+
+// material_textures 의 순서대로. base color, emissive 만 sRGB 이다
+const TEXTURE_SRGB: [bool; MATERIAL_TEXTURE_COUNT as usize] = [true, false, false, false, true];
+
+// MATERIAL_TEXTURE_COUNT 와 같은 순서
+fn material_textures(material: &Material) -> MaterialTextures {
+    [
+        material.base_color_texture,
+        material.metallic_roughness_texture,
+        material.normal_texture,
+        material.occlusion_texture,
+        material.emissive_texture,
+    ]
+}
+
+fn material_uniform(material: &Material) -> MaterialUniform {
+    // i 번째 bit 가 1 이면 i 번째 texture (MATERIAL_TEXTURE_COUNT 순서) 는 TEXCOORD_1 을 사용한다
+    let tex_coord_sets = material_textures(material)
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| t.map_or(false, |t| t.tex_coord == 1))
+        .fold(0, |bits, (i, _)| bits | (1 << i));
+
+    MaterialUniform {
+        base_color_factor: material.base_color_factor.into(),
+        emissive_factor: material.emissive_factor.into(),
+        metallic_factor: material.metallic_factor,
+        roughness_factor: material.roughness_factor,
+        normal_scale: material.normal_scale,
+        occlusion_strength: material.occlusion_strength,
+        has_normal_texture: material.normal_texture.is_some() as u32,
+        alpha_cutoff: material.alpha_cutoff,
+        tex_coord_sets,
+        _pad: [0; 2],
+    }
+}
+
+// glTF 의 기본 material. 흰색이고 metallic, roughness 가 1 이다
+// alpha mode (opaque), double sided (false) 는 PipelineKey 가 material 이 없을 때 쓰는 값과 같다
+fn default_material_uniform() -> MaterialUniform {
+    MaterialUniform {
+        base_color_factor: [1.0; 4],
+        emissive_factor: [0.0; 3],
+        metallic_factor: 1.0,
+        roughness_factor: 1.0,
+        normal_scale: 1.0,
+        occlusion_strength: 1.0,
+        has_normal_texture: 0,
+        alpha_cutoff: 0.5,
+        tex_coord_sets: 0,
+        _pad: [0; 2],
+    }
+}
+
+fn create_sampler(sampler: &crate::model::Sampler, deps: &WgpuDeps) -> wgpu::Sampler {
+    deps.device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("glTF Sampler"),
+        address_mode_u: sampler.address_mode_u,
+        address_mode_v: sampler.address_mode_v,
+        address_mode_w: wgpu::AddressMode::Repeat,
+        mag_filter: sampler.mag_filter,
+        min_filter: sampler.min_filter,
+        mipmap_filter: sampler.mipmap_filter.unwrap_or(wgpu::FilterMode::Nearest),
+        // mipmap 을 쓰지 않는 filter 는 base level 만 읽도록 한다
+        lod_max_clamp: if sampler.mipmap_filter.is_some() {
+            32.0
+        } else {
+            0.0
+        },
+        ..Default::default()
+    })
+}
+
+fn create_node(deps: &WgpuDeps) -> NodeResource {
+    let uniform_buffer = deps.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Uniform Buffer"),
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        size: std::mem::size_of::<NodeUniform>() as wgpu::BufferAddress,
+        mapped_at_creation: false,
+    });
+
+    let uniform_bind_group = deps.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: deps.node_uniform_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: uniform_buffer.as_entire_binding(),
+        }],
+        label: Some("primitive_transform_bind_group"),
+    });
+
+    NodeResource {
+        uniform_buffer,
+        uniform_bind_group,
+    }
+}
+
+fn create_primitive(
+    primitive: &MeshPrimitive,
+    material: Option<&Material>,
+    deps: &WgpuDeps,
+) -> PrimitiveResource {
+    let mut vertex_buffers = vec![create_vertex_buffer(
+        deps,
+        &primitive.positions,
+        "Vertex Position",
+    )];
+    if let Some(normals) = &primitive.normals {
+        vertex_buffers.push(create_vertex_buffer(deps, normals, "Vertex Normal"));
+    }
+    if let Some(tex_coords) = &primitive.tex_coords {
+        vertex_buffers.push(create_vertex_buffer(deps, tex_coords, "Vertex Tex Coord"));
+    }
+    if let Some(tex_coords) = &primitive.tex_coords_1 {
+        vertex_buffers.push(create_vertex_buffer(deps, tex_coords, "Vertex Tex Coord 1"));
+    }
+    if let Some(tangents) = &primitive.tangents {
+        vertex_buffers.push(create_vertex_buffer(deps, tangents, "Vertex Tangent"));
+    }
+    if let Some(colors) = &primitive.colors {
+        vertex_buffers.push(create_vertex_buffer(deps, colors, "Vertex Color"));
+    }
+    // pipeline 의 vertex layout 과 같이 joints, weights 가 모두 있을 때만 넘긴다
+    if let (Some(joints), Some(weights)) = (&primitive.joints, &primitive.weights) {
+        vertex_buffers.push(create_vertex_buffer(deps, joints, "Vertex Joints"));
+        vertex_buffers.push(create_vertex_buffer(deps, weights, "Vertex Weights"));
+    }
+
+    let indices = primitive.indices.as_ref().map(|indices| PrimitiveIndices {
+        buffer: deps
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Vertex Index"),
+                contents: indices.as_bytes(),
+                usage: wgpu::BufferUsages::INDEX,
+            }),
+        format: indices.format(),
+        count: indices.len(),
+    });
+
+    PrimitiveResource {
+        pipeline_key: pipeline_key(primitive, indices.as_ref(), material),
+        vertex_buffers,
+        indices,
+        num_vertices: primitive.positions.len(),
+    }
+}
+
+fn pipeline_key(
+    primitive: &MeshPrimitive,
+    indices: Option<&PrimitiveIndices>,
+    material: Option<&Material>,
+) -> PipelineKey {
+    PipelineKey::new(
+        primitive.vertex_attributes(),
+        primitive.topology,
+        indices.map(|indices| indices.format),
+        material,
+    )
+}
+
+fn create_vertex_buffer<T: bytemuck::Pod>(
+    deps: &WgpuDeps,
+    data: &[T],
+    label: &str,
+) -> wgpu::Buffer {
+    deps.device
+        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::cast_slice(data),
+            usage: wgpu::BufferUsages::VERTEX,
+        })
+}
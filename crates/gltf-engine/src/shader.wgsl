@@ -1,4 +1,5 @@
 // Vertex shader
+// NOTE: pipeline.rs 에서 #ifdef / #ifndef / #else / #endif 를 처리한 다음 compile 한다
 
 struct Camera {
     view_pos: vec4<f32>,
@@ -14,6 +15,14 @@ struct Node {
 struct Material {
     base_color_factor: vec4<f32>,
     emissive_factor: vec3<f32>,
+    metallic_factor: f32,
+    roughness_factor: f32,
+    normal_scale: f32,
+    occlusion_strength: f32,
+    has_normal_texture: u32,
+    alpha_cutoff: f32,
+    // i 번째 bit 가 1 이면 i 번째 texture (binding 순서) 는 TEXCOORD_1 을 사용한다
+    tex_coord_sets: u32,
 }
 
 @group(1) @binding(0)
@@ -24,8 +33,26 @@ var<uniform> node_uniform: Node;
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
+#ifdef HAS_NORMAL
     @location(1) normal: vec3<f32>,
-    @location(2) tex_coords: vec2<f32>,
+#endif
+#ifdef HAS_TEX_COORD_0
+    @location(2) tex_coord_0: vec2<f32>,
+#endif
+#ifdef HAS_TEX_COORD_1
+    @location(3) tex_coord_1: vec2<f32>,
+#endif
+#ifdef HAS_TANGENT
+    @location(4) tangent: vec4<f32>,
+#endif
+#ifdef HAS_COLOR
+    @location(5) color: vec4<f32>,
+#endif
+#ifdef HAS_JOINTS_WEIGHTS
+    // TODO: skinning
+    @location(6) joints: vec4<u32>,
+    @location(7) weights: vec4<f32>,
+#endif
 };
 
 struct VertexOutput {
@@ -33,6 +60,12 @@ struct VertexOutput {
     @location(0) tex_coords: vec2<f32>,
     @location(1) ws_position: vec3<f32>,
     @location(2) ws_normal: vec3<f32>,
+#ifdef HAS_TANGENT
+    // w 는 bitangent 의 부호
+    @location(3) ws_tangent: vec4<f32>,
+#endif
+    @location(4) tex_coords_1: vec2<f32>,
+    @location(5) color: vec4<f32>,
 };
 
 @vertex
@@ -41,10 +74,31 @@ fn vs_main(
 ) -> VertexOutput {
     var out: VertexOutput;
     out.ws_position = (node_uniform.model_mat * vec4<f32>(model.position, 1.0)).xyz;
-    // TODO: normal matrix
+#ifdef HAS_NORMAL
     out.ws_normal = normalize((node_uniform.normal_mat * vec4<f32>(model.normal, 0.0)).xyz);
+#else
+    out.ws_normal = vec3<f32>(0.0, 1.0, 0.0);
+#endif
+#ifdef HAS_TANGENT
+    let ws_tangent = normalize((node_uniform.model_mat * vec4<f32>(model.tangent.xyz, 0.0)).xyz);
+    out.ws_tangent = vec4<f32>(ws_tangent, model.tangent.w);
+#endif
     out.clip_position = camera.view_proj * vec4(out.ws_position, 1.0);
-    out.tex_coords = model.tex_coords;
+#ifdef HAS_TEX_COORD_0
+    out.tex_coords = model.tex_coord_0;
+#else
+    out.tex_coords = vec2<f32>(0.0);
+#endif
+#ifdef HAS_TEX_COORD_1
+    out.tex_coords_1 = model.tex_coord_1;
+#else
+    out.tex_coords_1 = vec2<f32>(0.0);
+#endif
+#ifdef HAS_COLOR
+    out.color = model.color;
+#else
+    out.color = vec4<f32>(1.0);
+#endif
     return out;
 }
 
@@ -52,15 +106,192 @@ fn vs_main(
 @group(0) @binding(0)
 var<uniform> material: Material;
 @group(0) @binding(1)
-var t_diffuse: texture_2d<f32>;
+var t_base_color: texture_2d<f32>;
 @group(0) @binding(2)
-var s_diffuse: sampler;
+var s_base_color: sampler;
+@group(0) @binding(3)
+var t_metallic_roughness: texture_2d<f32>;
+@group(0) @binding(4)
+var s_metallic_roughness: sampler;
+@group(0) @binding(5)
+var t_normal: texture_2d<f32>;
+@group(0) @binding(6)
+var s_normal: sampler;
+@group(0) @binding(7)
+var t_occlusion: texture_2d<f32>;
+@group(0) @binding(8)
+var s_occlusion: sampler;
+@group(0) @binding(9)
+var t_emissive: texture_2d<f32>;
+@group(0) @binding(10)
+var s_emissive: sampler;
+
+// Cook-Torrance (GGX + Smith + Schlick), glTF spec Appendix B 참고
+
+fn distribution_ggx(n_dot_h: f32, alpha: f32) -> f32 {
+    let alpha2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+    return alpha2 / (3.14159265 * d * d);
+}
+
+// Smith joint GGX visibility (G / (4 * n_dot_l * n_dot_v) 가 합쳐진 형태)
+fn visibility_smith_ggx(n_dot_l: f32, n_dot_v: f32, alpha: f32) -> f32 {
+    let alpha2 = alpha * alpha;
+    let ggx_v = n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - alpha2) + alpha2);
+    let ggx_l = n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - alpha2) + alpha2);
+    let ggx = ggx_v + ggx_l;
+    return select(0.0, 0.5 / ggx, ggx > 0.0);
+}
+
+fn fresnel_schlick(f0: vec3<f32>, v_dot_h: f32) -> vec3<f32> {
+    return f0 + (vec3<f32>(1.0) - f0) * pow(clamp(1.0 - v_dot_h, 0.0, 1.0), 5.0);
+}
+
+fn brdf(
+    n: vec3<f32>,
+    v: vec3<f32>,
+    l: vec3<f32>,
+    diffuse_color: vec3<f32>,
+    f0: vec3<f32>,
+    alpha: f32,
+) -> vec3<f32> {
+    let h = normalize(l + v);
+    let n_dot_l = clamp(dot(n, l), 0.0, 1.0);
+    let n_dot_v = clamp(abs(dot(n, v)), 0.001, 1.0);
+    let n_dot_h = clamp(dot(n, h), 0.0, 1.0);
+    let v_dot_h = clamp(dot(v, h), 0.0, 1.0);
+
+    let f = fresnel_schlick(f0, v_dot_h);
+    let diffuse = (vec3<f32>(1.0) - f) * diffuse_color / 3.14159265;
+    let specular = f * distribution_ggx(n_dot_h, alpha) * visibility_smith_ggx(n_dot_l, n_dot_v, alpha);
+    return (diffuse + specular) * n_dot_l;
+}
+
+// 환경광 (IBL) 대신 쓰는 analytic approximation (Karis, "Physically Based Shading on Mobile")
+fn env_brdf_approx(f0: vec3<f32>, roughness: f32, n_dot_v: f32) -> vec3<f32> {
+    let c0 = vec4<f32>(-1.0, -0.0275, -0.572, 0.022);
+    let c1 = vec4<f32>(1.0, 0.0425, 1.04, -0.04);
+    let r = roughness * c0 + c1;
+    let a004 = min(r.x * r.x, exp2(-9.28 * n_dot_v)) * r.x + r.y;
+    let ab = vec2<f32>(-1.04, 1.04) * a004 + r.zw;
+    return f0 * ab.x + ab.y;
+}
+
+// vertex tangent 가 없을 때 normal map 의 texcoord 를 따라가는 tangent (dP/du) 를 화면 공간 미분으로부터 계산한다
+// bitangent 는 glTF-Sample-Viewer 와 같이 cross(n, t) 로 정한다. dP/dv 를 쓰면 glTF 의 UV 원점이 왼쪽 위라서 반대 방향이 된다
+// NOTE: GL backend 에서는 함수가 vertex shader 에도 포함되므로, dpdx/dpdy 는 fs_main 에서 호출해서 넘겨준다
+fn derivative_tangent(
+    n: vec3<f32>,
+    dp1: vec3<f32>,
+    dp2: vec3<f32>,
+    duv1: vec2<f32>,
+    duv2: vec2<f32>,
+) -> vec3<f32> {
+    let det = duv1.x * duv2.y - duv2.x * duv1.y;
+    let t = (dp1 * duv2.y - dp2 * duv1.y) * sign(det);
+    return normalize(t - n * dot(n, t));
+}
+
+// texture_index 는 material texture 의 binding 순서 (base color, metallic-roughness, normal, occlusion, emissive)
+fn texture_uv(in: VertexOutput, texture_index: u32) -> vec2<f32> {
+    let use_tex_coord_1 = (material.tex_coord_sets & (1u << texture_index)) != 0u;
+    return select(in.tex_coords, in.tex_coords_1, use_tex_coord_1);
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let cutoff = color < vec3<f32>(0.0031308);
+    let higher = vec3<f32>(1.055) * pow(color, vec3<f32>(1.0 / 2.4)) - vec3<f32>(0.055);
+    let lower = color * vec3<f32>(12.92);
+    return select(higher, lower, cutoff);
+}
 
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let brightness = 0.3 + 0.7 * dot(in.ws_normal.xyz, -camera.view_front.xyz);
-    let sampled = textureSample(t_diffuse, s_diffuse, in.tex_coords);
-    let color = material.emissive_factor + brightness * sampled.rgb * material.base_color_factor.rgb;
-    let alpha = sampled.a * material.base_color_factor.a;
-    return vec4<f32>(color, alpha);
+fn fs_main(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+    let base_color_uv = texture_uv(in, 0u);
+    let metallic_roughness_uv = texture_uv(in, 1u);
+    let normal_uv = texture_uv(in, 2u);
+    let occlusion_uv = texture_uv(in, 3u);
+    let emissive_uv = texture_uv(in, 4u);
+
+    let base_color = textureSample(t_base_color, s_base_color, base_color_uv) * material.base_color_factor * in.color;
+
+#ifdef HAS_NORMAL
+    // metallic 은 B, roughness 는 G 채널
+    let mr = textureSample(t_metallic_roughness, s_metallic_roughness, metallic_roughness_uv);
+    let metallic = clamp(material.metallic_factor * mr.b, 0.0, 1.0);
+    let roughness = clamp(material.roughness_factor * mr.g, 0.04, 1.0);
+    let alpha = roughness * roughness;
+
+    let ao = textureSample(t_occlusion, s_occlusion, occlusion_uv).r;
+    let occlusion = 1.0 + material.occlusion_strength * (ao - 1.0);
+
+    let emissive = textureSample(t_emissive, s_emissive, emissive_uv).rgb * material.emissive_factor;
+
+    var n = normalize(in.ws_normal);
+    // double sided material 의 뒷면 (single sided 는 cull 되므로 여기까지 오지 않는다)
+    if (!front_facing) {
+        n = -n;
+    }
+    let sampled_normal = textureSample(t_normal, s_normal, normal_uv).xyz * 2.0 - vec3<f32>(1.0);
+    let ts_normal = normalize(sampled_normal * vec3<f32>(material.normal_scale, material.normal_scale, 1.0));
+#ifdef HAS_TANGENT
+    var t = normalize(in.ws_tangent.xyz - n * dot(n, in.ws_tangent.xyz));
+    // 뒷면이면 n 이 이미 뒤집혀 있으므로 b 도 뒤집힌다. 아래에서 t 도 뒤집어서 tangent frame 전체를 뒤집는다
+    let b = cross(n, t) * in.ws_tangent.w;
+#else
+    var t = derivative_tangent(
+        n,
+        dpdx(in.ws_position),
+        dpdy(in.ws_position),
+        dpdx(normal_uv),
+        dpdy(normal_uv),
+    );
+    let b = cross(n, t);
+#endif
+    if (!front_facing) {
+        t = -t;
+    }
+    let perturbed = normalize(mat3x3<f32>(t, b, n) * ts_normal);
+    if (material.has_normal_texture != 0u) {
+        n = perturbed;
+    }
+
+    let v = normalize(camera.view_pos.xyz - in.ws_position);
+
+    let f0 = mix(vec3<f32>(0.04), base_color.rgb, metallic);
+    let diffuse_color = base_color.rgb * (1.0 - metallic);
+
+    // 씬에 광원 정보가 없으므로, 카메라에 붙은 headlight 와 위쪽에서 오는 key light 를 사용한다
+    let headlight_dir = normalize(-camera.view_front.xyz + vec3<f32>(0.0, 0.3, 0.0));
+    let key_light_dir = normalize(vec3<f32>(0.5, 1.0, 0.3));
+    var color = brdf(n, v, headlight_dir, diffuse_color, f0, alpha) * 2.0;
+    color += brdf(n, v, key_light_dir, diffuse_color, f0, alpha) * 1.5;
+
+    // hemisphere ambient
+    let n_dot_v = clamp(abs(dot(n, v)), 0.001, 1.0);
+    let sky = vec3<f32>(0.35, 0.38, 0.45);
+    let ground = vec3<f32>(0.15, 0.13, 0.12);
+    let ambient = mix(ground, sky, n.y * 0.5 + 0.5);
+    let ambient_specular = env_brdf_approx(f0, roughness, n_dot_v);
+    color += (diffuse_color + ambient_specular) * ambient * occlusion;
+
+    color += emissive;
+#else
+    // normal 이 없는 point, line 은 lighting 없이 base color 로 그린다
+    let color = base_color.rgb;
+#endif
+
+#ifdef ALPHA_BLEND
+    let opacity = base_color.a;
+#else
+#ifdef ALPHA_MASK
+    if (base_color.a < material.alpha_cutoff) {
+        discard;
+    }
+#endif
+    let opacity = 1.0;
+#endif
+
+    // color target 이 sRGB format 이 아니므로 직접 encoding 한다
+    return vec4<f32>(linear_to_srgb(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0))), opacity);
 }
@@ -0,0 +1,47 @@
+use crate::import::{load_gltf_slice, prepare_gltf, GltfRoot, ImportOptions};
+use crate::model::ImportedGltf;
+
+// 테스트용 glTF 를 파일 없이 만든다
+// json 에는 asset, buffers 를 빼고 적는다. buffer 는 bin 하나뿐이다 (GLB 의 BIN chunk)
+
+pub fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+    let fields = json.trim().strip_prefix('{').expect("JSON object");
+    let json = format!(
+        r#"{{ "asset": {{ "version": "2.0" }}, "buffers": [{{ "byteLength": {} }}],{}"#,
+        bin.len(),
+        fields
+    );
+
+    // chunk 는 4 byte 단위로 맞춘다. JSON 은 공백, BIN 은 0 으로 채운다
+    let mut json = json.into_bytes();
+    json.resize((json.len() + 3) / 4 * 4, b' ');
+    let mut bin = bin.to_vec();
+    bin.resize((bin.len() + 3) / 4 * 4, 0);
+
+    let length = 12 + 8 + json.len() + 8 + bin.len();
+    let mut result = Vec::with_capacity(length);
+    result.extend_from_slice(b"glTF");
+    result.extend_from_slice(&2u32.to_le_bytes());
+    result.extend_from_slice(&(length as u32).to_le_bytes());
+    result.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    result.extend_from_slice(b"JSON");
+    result.extend_from_slice(&json);
+    result.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+    result.extend_from_slice(b"BIN\0");
+    result.extend_from_slice(&bin);
+    result
+}
+
+pub fn load(json: &str, bin: &[u8]) -> GltfRoot {
+    load_gltf_slice(&glb(json, bin)).unwrap()
+}
+
+pub fn prepare(json: &str, bin: &[u8], options: &ImportOptions) -> ImportedGltf {
+    prepare_gltf(load(json, bin), options, &mut |_| Ok(()))
+        .unwrap()
+        .model
+}
+
+pub fn f32_bytes(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
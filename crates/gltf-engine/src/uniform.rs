@@ -0,0 +1,55 @@
+// shader 의 uniform buffer 와 같은 layout 의 struct 들
+// bytemuck 의 Pod derive 가 padding 검사용으로 만드는 함수와 struct 는 어디서도 쓰이지 않아서
+// bytemuck_derive 버전에 따라 dead_code 경고가 난다. 이 module 안에서만 허용한다
+#![allow(dead_code)]
+
+use crate::camera::{Camera, Projection};
+use cgmath::*;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct NodeUniform {
+    pub model_mat: [[f32; 4]; 4],
+    pub normal_mat: [[f32; 4]; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+    pub base_color_factor: [f32; 4],
+    pub emissive_factor: [f32; 3],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    pub has_normal_texture: u32,
+    pub alpha_cutoff: f32,
+    // i 번째 bit 가 1 이면 i 번째 texture 는 TEXCOORD_1 을 사용한다
+    pub tex_coord_sets: u32,
+    pub _pad: [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    pub view_position: [f32; 4],
+    pub view_front: [f32; 4],
+    pub view_proj: [[f32; 4]; 4],
+}
+
+impl CameraUniform {
+    pub fn new() -> Self {
+        Self {
+            view_position: Vector4::zero().into(),
+            view_front: Vector4::unit_x().into(),
+            view_proj: Matrix4::identity().into(),
+        }
+    }
+
+    pub fn update_view_proj(&mut self, camera: &Camera, projection: &Projection) {
+        self.view_position = camera.position.to_homogeneous().into();
+        let f = camera.front();
+        self.view_front = Vector4::new(f.x, f.y, f.z, 0.0).into();
+        self.view_proj = (projection.calc_matrix() * camera.calc_matrix()).into();
+    }
+}
@@ -19,6 +19,9 @@
 - Mutation 에 대한 통제권 확보 (순서를 조작한다던가, 일부 command 는 일부러 누락시킨다던가, ...)
  */
 
+use crate::undo_manager::Undoable;
+use gltf_engine::cgmath::{Euler, InnerSpace, Quaternion, Rad};
+use gltf_engine::model::{EulerOrder, Node};
 use gltf_engine::{Engine, InputEvent};
 use uuid::Uuid;
 
@@ -42,12 +45,24 @@ pub enum TodoListCommand {
     },
 }
 
-// TODO: und
 #[derive(Clone, Debug)]
 pub struct UpdateFloatCommand {
     pub node_id: Uuid,
     pub value: f32,
-    // commit: bool,
+}
+
+// 단위 quaternion 이 아니어도 되고, 적용할 때 정규화한다
+#[derive(Clone, Debug)]
+pub struct UpdateRotationCommand {
+    pub node_id: Uuid,
+    pub value: Quaternion<f32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct UpdateEulerRotationCommand {
+    pub node_id: Uuid,
+    pub angles: Euler<Rad<f32>>,
+    pub order: EulerOrder,
 }
 
 #[derive(Clone, Debug)]
@@ -56,9 +71,13 @@ pub enum EngineCommand {
     UpdatePositionX(UpdateFloatCommand),
     UpdatePositionY(UpdateFloatCommand),
     UpdatePositionZ(UpdateFloatCommand),
+    UpdateRotation(UpdateRotationCommand),
+    UpdateRotationEuler(UpdateEulerRotationCommand),
     UpdateScaleX(UpdateFloatCommand),
     UpdateScaleY(UpdateFloatCommand),
     UpdateScaleZ(UpdateFloatCommand),
+    SetActiveScene(Uuid),
+    SetSelection(Vec<Uuid>),
 }
 
 pub struct EngineModel<'a> {
@@ -74,67 +93,90 @@ impl<'a> EngineModel<'a> {
         self.engine
     }
 
-    pub fn process_command(&mut self, command: EngineCommand) {
-        // TODO: undo
+    // command 가 만들어진 뒤에 다른 glTF 를 불러왔으면 node 가 없을 수 있다
+    fn node_mut(&mut self, node_id: Uuid) -> Option<&mut Node> {
+        self.engine.model_root_mut()?.nodes.get_mut(&node_id)
+    }
+
+    // 값을 바꾸고, 이전 값으로 되돌리는 command 를 반환한다
+    fn update_float(
+        &mut self,
+        f: UpdateFloatCommand,
+        field: impl FnOnce(&mut Node) -> &mut f32,
+        command: fn(UpdateFloatCommand) -> EngineCommand,
+    ) -> Option<EngineCommand> {
+        let node = self.node_mut(f.node_id)?;
+        let value = std::mem::replace(field(node), f.value);
+        Some(command(UpdateFloatCommand {
+            node_id: f.node_id,
+            value,
+        }))
+    }
+
+    fn update_rotation(
+        &mut self,
+        node_id: Uuid,
+        rotation: Quaternion<f32>,
+    ) -> Option<EngineCommand> {
+        let node = self.node_mut(node_id)?;
+        let value = std::mem::replace(&mut node.transform.rotation, rotation);
+        Some(EngineCommand::UpdateRotation(UpdateRotationCommand {
+            node_id,
+            value,
+        }))
+    }
+
+    // 되돌릴 수 있는 command 면 원상복구하는 command 를 반환한다
+    pub fn process_command(&mut self, command: EngineCommand) -> Option<EngineCommand> {
         use EngineCommand::*;
         match command {
             InputEvent(input_event) => {
                 self.engine.input(&input_event);
+                None
             }
             UpdatePositionX(f) => {
-                let node = self
-                    .engine
-                    .model_root_mut()
-                    .nodes
-                    .get_mut(&f.node_id)
-                    .unwrap();
-                node.transform.position.x = f.value;
+                self.update_float(f, |node| &mut node.transform.position.x, UpdatePositionX)
             }
             UpdatePositionY(f) => {
-                let node = self
-                    .engine
-                    .model_root_mut()
-                    .nodes
-                    .get_mut(&f.node_id)
-                    .unwrap();
-                node.transform.position.y = f.value;
+                self.update_float(f, |node| &mut node.transform.position.y, UpdatePositionY)
             }
             UpdatePositionZ(f) => {
-                let node = self
-                    .engine
-                    .model_root_mut()
-                    .nodes
-                    .get_mut(&f.node_id)
-                    .unwrap();
-                node.transform.position.z = f.value;
+                self.update_float(f, |node| &mut node.transform.position.z, UpdatePositionZ)
+            }
+            UpdateRotation(r) => {
+                // 길이가 0 인 quaternion 은 회전을 나타내지 않으므로 무시한다
+                if r.value.magnitude2() < f32::EPSILON {
+                    return None;
+                }
+                self.update_rotation(r.node_id, r.value.normalize())
+            }
+            UpdateRotationEuler(r) => {
+                self.update_rotation(r.node_id, r.order.to_quaternion(r.angles))
             }
             UpdateScaleX(f) => {
-                let node = self
-                    .engine
-                    .model_root_mut()
-                    .nodes
-                    .get_mut(&f.node_id)
-                    .unwrap();
-                node.transform.scale.x = f.value;
+                self.update_float(f, |node| &mut node.transform.scale.x, UpdateScaleX)
             }
             UpdateScaleY(f) => {
-                let node = self
-                    .engine
-                    .model_root_mut()
-                    .nodes
-                    .get_mut(&f.node_id)
-                    .unwrap();
-                node.transform.scale.y = f.value;
+                self.update_float(f, |node| &mut node.transform.scale.y, UpdateScaleY)
             }
             UpdateScaleZ(f) => {
-                let node = self
-                    .engine
-                    .model_root_mut()
-                    .nodes
-                    .get_mut(&f.node_id)
-                    .unwrap();
-                node.transform.scale.z = f.value;
+                self.update_float(f, |node| &mut node.transform.scale.z, UpdateScaleZ)
+            }
+            // 보는 scene 을 바꾸는 것은 편집이 아니므로 undo 하지 않는다
+            SetActiveScene(scene_id) => {
+                self.engine.set_active_scene(scene_id);
+                None
+            }
+            SetSelection(node_ids) => {
+                self.engine.set_selection(&node_ids);
+                None
             }
         }
     }
 }
+
+impl Undoable<EngineCommand> for EngineModel<'_> {
+    fn apply(&mut self, command: EngineCommand) -> Option<EngineCommand> {
+        self.process_command(command)
+    }
+}
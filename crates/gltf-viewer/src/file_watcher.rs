@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// 파일의 수정 시각을 주기적으로 확인한다
+// export 도구는 .gltf, .bin, image 를 차례로 쓰므로, 바뀐 뒤 한 주기 동안 더 바뀌지 않을 때 변경으로 본다
+
+pub const POLL_INTERVAL: instant::Duration = instant::Duration::from_millis(500);
+
+pub struct FileWatcher {
+    // 파일이 없으면 None
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    last_poll: instant::Instant,
+    changing: bool,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// 불러오기 시작할 때의 수정 시각. 불러오는 동안 저장된 변경을 놓치지 않도록 FileWatcher 의 기준으로 쓴다
+pub struct FileSnapshot {
+    taken_at: SystemTime,
+    files: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+impl FileSnapshot {
+    pub fn new(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        Self {
+            taken_at: SystemTime::now(),
+            files: paths
+                .into_iter()
+                .map(|path| {
+                    let modified = modified(&path);
+                    (path, modified)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl FileWatcher {
+    pub fn new(paths: Vec<PathBuf>, snapshot: &FileSnapshot) -> Self {
+        let now = SystemTime::now();
+        Self {
+            files: paths
+                .into_iter()
+                .map(|path| {
+                    let modified = match snapshot.files.get(&path) {
+                        Some(modified) => *modified,
+                        // 불러온 뒤에야 알게 된 파일 (glTF 가 참조하는 buffer, image). 불러오는 동안 바뀌었으면
+                        // 기준을 None 으로 두어서 다음 poll 에서 변경으로 보이게 한다
+                        // 수정 시각이 지금보다 뒤인 파일 (시계가 다른 곳에서 복사됨 등) 은 불러오는 동안 바뀐 것이 아니므로
+                        // 본 값을 그대로 기준으로 쓴다. 그렇지 않으면 다시 불러올 때마다 변경으로 보인다
+                        None => modified(&path)
+                            .filter(|modified| *modified < snapshot.taken_at || *modified > now),
+                    };
+                    (path, modified)
+                })
+                .collect(),
+            last_poll: instant::Instant::now(),
+            changing: false,
+        }
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.files.iter().map(|(path, _)| path)
+    }
+
+    // 변경이 끝났으면 true. 매 frame 호출해도 POLL_INTERVAL 마다 한 번만 확인한다
+    pub fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = instant::Instant::now();
+
+        let mut changed = false;
+        for (path, last_modified) in &mut self.files {
+            let modified = modified(path);
+            if modified != *last_modified {
+                *last_modified = modified;
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.changing = true;
+            false
+        } else {
+            std::mem::take(&mut self.changing)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // POLL_INTERVAL 을 기다리지 않고 바로 확인한다
+    fn poll_now(watcher: &mut FileWatcher) -> bool {
+        watcher.last_poll -= POLL_INTERVAL;
+        watcher.poll()
+    }
+
+    #[test]
+    fn future_modified_time_is_not_a_change() {
+        let path =
+            std::env::temp_dir().join(format!("gltf-viewer-watch-{}.bin", std::process::id()));
+        let file = std::fs::File::create(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(3600))
+            .unwrap();
+
+        // 불러온 뒤에야 알게 된 파일처럼 snapshot 에는 없다
+        let snapshot = FileSnapshot::new(Vec::new());
+        let mut watcher = FileWatcher::new(vec![path.clone()], &snapshot);
+        let changed = (0..3).any(|_| poll_now(&mut watcher));
+        std::fs::remove_file(&path).unwrap();
+        assert!(!changed);
+    }
+
+    #[test]
+    fn file_modified_while_loading_is_a_change() {
+        let path =
+            std::env::temp_dir().join(format!("gltf-viewer-load-{}.bin", std::process::id()));
+        let snapshot = FileSnapshot::new(Vec::new());
+        // file system 의 시각은 SystemTime::now 보다 조금 늦을 수 있으므로 직접 정한다
+        let file = std::fs::File::create(&path).unwrap();
+        file.set_modified(SystemTime::now()).unwrap();
+
+        let mut watcher = FileWatcher::new(vec![path.clone()], &snapshot);
+        // 바뀐 것을 본 다음, 한 주기 동안 더 바뀌지 않으면 변경으로 본다
+        let changed = (0..2).map(|_| poll_now(&mut watcher)).collect::<Vec<_>>();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(changed, vec![false, true]);
+    }
+}
@@ -1,17 +1,27 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
 mod command;
+mod file_watcher;
 mod model;
 mod ui;
 mod undo_manager;
 
 use gltf_engine::wgpu;
-use gltf_engine::Engine;
+use gltf_engine::{Engine, ImportError, LoadEvent, LoadProgress, LoadSource, LoadTask};
 
 use crate::ui::framework::*;
 use crate::ui::root::{RootViewContext, RootViewState};
 use eframe::egui;
 use crate::command::{EngineCommand, EngineModel};
+use crate::file_watcher::{FileSnapshot, FileWatcher};
+use crate::undo_manager::UndoManager;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+// eframe storage 에 저장된다
+const RECENT_FILES_KEY: &str = "recent_files";
+const MAX_RECENT_FILES: usize = 10;
 
 fn main() {
     // Log to stdout (if you run with `RUST_LOG=debug`).
@@ -19,6 +29,7 @@ fn main() {
 
     let options = eframe::NativeOptions {
         initial_window_size: Some(egui::vec2(1024.0, 768.0)),
+        drag_and_drop_support: true,
         ..Default::default()
     };
     eframe::run_native(
@@ -137,8 +148,30 @@ impl PaintResource {
     }
 }
 
+// worker thread 에서 불러오는 중인 파일
+struct Loading {
+    path: PathBuf,
+    // drop 되면 취소된다
+    task: LoadTask,
+    progress: Option<LoadProgress>,
+    // 파일이 바뀌어서 다시 불러오는 중
+    reload: bool,
+    // 불러오기를 마치면 FileWatcher 의 기준이 된다
+    snapshot: FileSnapshot,
+}
+
 struct MyApp {
     root_view_state: RootViewState,
+    loading: Option<Loading>,
+    // 마지막으로 불러온 파일. 이 파일과 참조하는 파일들이 바뀌면 다시 불러온다
+    loaded_path: Option<PathBuf>,
+    file_watcher: Option<FileWatcher>,
+    // 확인 버튼을 누를 때까지 창으로 보여준다
+    load_error: Option<ImportError>,
+    // 최근에 연 것부터
+    recent_files: Vec<PathBuf>,
+    // property panel 에서 한 편집. 불러온 model 이 바뀌면 비운다
+    undo_manager: UndoManager<EngineCommand>,
 }
 
 impl MyApp {
@@ -150,40 +183,254 @@ impl MyApp {
 
         let paint_resource = PaintResource::new(&device, &queue, target_format);
 
+        let mut app = MyApp {
+            root_view_state: RootViewState::new(),
+            loading: None,
+            loaded_path: None,
+            file_watcher: None,
+            load_error: None,
+            recent_files: cc
+                .storage
+                .and_then(|storage| eframe::get_value(storage, RECENT_FILES_KEY))
+                .unwrap_or_default(),
+            undo_manager: UndoManager::new(),
+        };
+
+        // 실행 인자로 glTF 경로가 주어지면 바로 불러온다
+        if let Some(path) = std::env::args().nth(1) {
+            app.open_file(&paint_resource.engine, path.into());
+        }
+
         wgpu_render_state
             .renderer
             .write()
             .paint_callback_resources
             .insert(paint_resource);
 
-        Some(MyApp {
-            root_view_state: RootViewState::new(),
-        })
+        Some(app)
+    }
+
+    // 이미 불러오는 중인 파일이 있으면 취소하고 새로 시작한다
+    fn open_file(&mut self, engine: &Engine, path: PathBuf) {
+        self.start_loading(engine, path, false);
+    }
+
+    fn start_loading(&mut self, engine: &Engine, path: PathBuf, reload: bool) {
+        // 다시 불러올 때는 지금 보고 있는 파일들도 알고 있다
+        let watched_files = self
+            .file_watcher
+            .iter()
+            .filter(|_| reload)
+            .flat_map(|file_watcher| file_watcher.paths().cloned());
+        let snapshot = FileSnapshot::new(std::iter::once(path.clone()).chain(watched_files));
+        let task = engine.spawn_load_task(LoadSource::Path(path.clone()));
+        self.loading = Some(Loading {
+            path,
+            task,
+            progress: None,
+            reload,
+            snapshot,
+        });
+    }
+
+    // 다시 불러오는 중에 또 바뀌면 처음부터 다시 불러온다
+    fn watch_file(&mut self, engine: &Engine) {
+        if matches!(&self.loading, Some(loading) if !loading.reload) {
+            return;
+        }
+        let (Some(path), Some(file_watcher)) = (&self.loaded_path, &mut self.file_watcher) else {
+            return;
+        };
+        if file_watcher.poll() {
+            log::info!("{} changed. Reloading", path.display());
+            self.start_loading(engine, path.clone(), true);
+        }
+    }
+
+    // worker 가 준비를 마치면 GPU 에 올리는 것만 render thread 에서 한다
+    fn poll_loading(&mut self, engine: &mut Engine, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let Some(loading) = &mut self.loading else {
+            return;
+        };
+        let mut result = None;
+        while let Some(event) = loading.task.poll_event() {
+            match event {
+                LoadEvent::Progress(progress) => loading.progress = Some(progress),
+                LoadEvent::Finished(prepared) => {
+                    result = Some(prepared);
+                    break;
+                }
+            }
+        }
+        let Some(result) = result else {
+            return;
+        };
+
+        let Loading {
+            path,
+            reload,
+            snapshot,
+            ..
+        } = self.loading.take().unwrap();
+        // 같은 파일을 다시 불러올 때는 glTF index 가 같은 node, scene 을 같은 것으로 본다
+        let old_node_indices = if reload {
+            node_gltf_indices(engine)
+        } else {
+            Vec::new()
+        };
+        let old_active_scene_index = engine
+            .model_root()
+            .and_then(|model_root| model_root.active_scene())
+            .map(|scene| scene.gltf_index())
+            .filter(|_| reload);
+        let result = result.and_then(|prepared| {
+            let source_files = prepared.source_files(&path);
+            engine.load_prepared(device, queue, *prepared)?;
+            Ok(source_files)
+        });
+        match result {
+            Ok(source_files) => {
+                // 다시 불러온 경우에도 편집한 내용은 파일 내용으로 덮어써진다
+                self.undo_manager.clear();
+                self.file_watcher = Some(FileWatcher::new(source_files, &snapshot));
+                if reload {
+                    let new_node_ids: HashMap<usize, Uuid> = node_gltf_indices(engine)
+                        .into_iter()
+                        .map(|(id, index)| (index, id))
+                        .collect();
+                    let node_ids: HashMap<Uuid, Uuid> = old_node_indices
+                        .into_iter()
+                        .filter_map(|(id, index)| Some((id, *new_node_ids.get(&index)?)))
+                        .collect();
+                    self.root_view_state.nodes_reloaded(&node_ids);
+
+                    let active_scene_id = engine.model_root().and_then(|model_root| {
+                        model_root
+                            .scenes
+                            .values()
+                            .find(|scene| Some(scene.gltf_index()) == old_active_scene_index)
+                            .map(|scene| scene.id)
+                    });
+                    if let Some(scene_id) = active_scene_id {
+                        engine.set_active_scene(scene_id);
+                    }
+                } else {
+                    self.root_view_state.nodes_replaced();
+                }
+                engine.set_selection(&self.root_view_state.selected_node_ids());
+                self.loaded_path = Some(path.clone());
+                self.load_error = None;
+                self.recent_files.retain(|p| p != &path);
+                self.recent_files.insert(0, path);
+                self.recent_files.truncate(MAX_RECENT_FILES);
+            }
+            Err(ImportError::Cancelled) => {}
+            Err(e) => {
+                log::error!("Failed to load glTF {}: {}", path.display(), e);
+                self.load_error = Some(e);
+            }
+        }
+    }
+
+    fn show_load_error(&mut self, ctx: &egui::Context) {
+        let Some(error) = &self.load_error else {
+            return;
+        };
+        let mut dismissed = false;
+        egui::Window::new("Failed to load glTF")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(error.to_string());
+                dismissed = ui.button("OK").clicked();
+            });
+        if dismissed {
+            self.load_error = None;
+        }
     }
 }
 
+fn node_gltf_indices(engine: &Engine) -> Vec<(Uuid, usize)> {
+    engine.model_root().map_or(Vec::new(), |model_root| {
+        model_root
+            .nodes
+            .values()
+            .map(|node| (node.id, node.gltf_index()))
+            .collect()
+    })
+}
+
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        self.show_load_error(ctx);
+
         let (should_close, request_repaint) = {
-            let mut write_lock = frame.wgpu_render_state().unwrap().renderer.write();
+            let render_state = frame.wgpu_render_state().unwrap();
+            let mut write_lock = render_state.renderer.write();
             let paint_resource = write_lock
                 .paint_callback_resources
                 .get_mut::<PaintResource>()
                 .unwrap();
+            self.watch_file(&paint_resource.engine);
+            self.poll_loading(
+                &mut paint_resource.engine,
+                &render_state.device,
+                &render_state.queue,
+            );
             let mut engine_model = EngineModel::new(&mut paint_resource.engine);
             let mut rvc = RootViewContextImpl {
                 engine_model: &engine_model,
+                recent_files: &self.recent_files,
+                loading: self.loading.as_ref(),
+                undo_manager: &self.undo_manager,
                 commands: Vec::new(),
+                open_file: None,
+                cancel_loading: false,
+                undo: false,
+                redo: false,
+                begin_undo_group: false,
+                end_undo_group: false,
                 exit: false,
                 repaint: false,
             };
             egui::Area::new("Dumb Area").show(ctx, |ui| {
                 self.root_view_state.update(ui, &mut rvc);
             });
-            let (exit, repaint) = (rvc.exit, rvc.repaint);
+            let (commands, exit, repaint, open_file, cancel_loading, undo, redo) = (
+                rvc.commands,
+                rvc.exit,
+                rvc.repaint,
+                rvc.open_file,
+                rvc.cancel_loading,
+                rvc.undo,
+                rvc.redo,
+            );
+            let (begin_undo_group, end_undo_group) = (rvc.begin_undo_group, rvc.end_undo_group);
+
+            // property panel 의 DragValue 를 끄는 동안 바뀐 값들은 undo 한 단계로 묶는다
+            if begin_undo_group {
+                self.undo_manager.begin_group();
+            }
+            for command in commands {
+                if let Some(inverse) = engine_model.process_command(command) {
+                    self.undo_manager.push_undo(inverse);
+                }
+            }
+            if end_undo_group {
+                self.undo_manager.end_group();
+            }
+            if undo {
+                self.undo_manager.undo(&mut engine_model);
+            }
+            if redo {
+                self.undo_manager.redo(&mut engine_model);
+            }
 
-            for command in rvc.commands {
-                engine_model.process_command(command);
+            if cancel_loading {
+                self.loading = None;
+            }
+            if let Some(path) = open_file {
+                self.open_file(&paint_resource.engine, path);
             }
 
             (exit, repaint)
@@ -193,15 +440,31 @@ impl eframe::App for MyApp {
             frame.close();
         }
 
-        if request_repaint {
+        // 불러오는 동안에는 입력이 없어도 진행 상황을 확인해야 한다
+        if request_repaint || self.loading.is_some() {
             ctx.request_repaint();
+        } else if self.file_watcher.is_some() {
+            ctx.request_repaint_after(file_watcher::POLL_INTERVAL);
         }
     }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, RECENT_FILES_KEY, &self.recent_files);
+    }
 }
 
 struct RootViewContextImpl<'a> {
     engine_model: &'a EngineModel<'a>,
+    recent_files: &'a [PathBuf],
+    loading: Option<&'a Loading>,
+    undo_manager: &'a UndoManager<EngineCommand>,
     commands: Vec<EngineCommand>,
+    open_file: Option<PathBuf>,
+    cancel_loading: bool,
+    undo: bool,
+    redo: bool,
+    begin_undo_group: bool,
+    end_undo_group: bool,
     exit: bool,
     repaint: bool,
 }
@@ -224,6 +487,32 @@ impl ViewContext<(), EngineCommand> for RootViewContextImpl<'_> {
     }
 }
 
+impl UndoableViewContext for RootViewContextImpl<'_> {
+    fn can_undo(&self) -> bool {
+        self.undo_manager.can_undo()
+    }
+
+    fn can_redo(&self) -> bool {
+        self.undo_manager.can_redo()
+    }
+
+    fn undo_requested(&self) -> bool {
+        self.undo
+    }
+
+    fn redo_requested(&self) -> bool {
+        self.redo
+    }
+
+    fn request_undo(&mut self) {
+        self.undo = true;
+    }
+
+    fn request_redo(&mut self) {
+        self.redo = true;
+    }
+}
+
 impl RootViewContext for RootViewContextImpl<'_> {
     fn engine_model(&self) -> &EngineModel {
         self.engine_model
@@ -233,4 +522,31 @@ impl RootViewContext for RootViewContextImpl<'_> {
         self.repaint = true;
     }
 
+    fn recent_files(&self) -> &[PathBuf] {
+        self.recent_files
+    }
+
+    fn request_open_file(&mut self, path: PathBuf) {
+        self.open_file = Some(path);
+    }
+
+    fn loading_path(&self) -> Option<&Path> {
+        self.loading.map(|loading| loading.path.as_path())
+    }
+
+    fn loading_progress(&self) -> Option<LoadProgress> {
+        self.loading.and_then(|loading| loading.progress)
+    }
+
+    fn request_cancel_loading(&mut self) {
+        self.cancel_loading = true;
+    }
+
+    fn begin_undo_group(&mut self) {
+        self.begin_undo_group = true;
+    }
+
+    fn end_undo_group(&mut self) {
+        self.end_undo_group = true;
+    }
 }
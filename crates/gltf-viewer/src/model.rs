@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use crate::command::TodoListCommand;
+use crate::undo_manager::Undoable;
 
 pub struct TodoItem {
     pub id: uuid::Uuid,
@@ -55,6 +56,12 @@ impl TodoListModel {
     }
 }
 
+impl Undoable<TodoListCommand> for TodoListModel {
+    fn apply(&mut self, command: TodoListCommand) -> Option<TodoListCommand> {
+        Some(self.process_command(command))
+    }
+}
+
 impl From<Vec<TodoItem>> for TodoListModel {
     fn from(items: Vec<TodoItem>) -> Self {
         let mut map = HashMap::new();
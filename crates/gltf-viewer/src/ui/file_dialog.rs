@@ -0,0 +1,180 @@
+use crate::ui::framework::{ViewContext, ViewState};
+use eframe::egui;
+use std::path::{Path, PathBuf};
+
+// OS 기본 file dialog 를 쓰려면 platform 별 의존성 (GTK 등) 이 필요해서 egui 로 간단히 만든다
+// 디렉토리와 glTF 파일만 보여준다
+
+pub enum FileDialogCommand {
+    Open(PathBuf),
+    Close,
+}
+
+enum Event {
+    DirectoryEntered(PathBuf),
+    FileSelected(PathBuf),
+    FileChosen(PathBuf),
+    PathSubmitted,
+    Cancelled,
+}
+
+struct DirectoryEntry {
+    name: String,
+    path: PathBuf,
+    is_dir: bool,
+}
+
+pub struct FileDialogViewState {
+    directory: PathBuf,
+    entries: Vec<DirectoryEntry>,
+    path_text: String,
+    error: Option<String>,
+    events: Vec<Event>,
+}
+
+pub fn is_gltf_path(path: &Path) -> bool {
+    let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    ext.eq_ignore_ascii_case("gltf") || ext.eq_ignore_ascii_case("glb")
+}
+
+impl FileDialogViewState {
+    pub fn new(directory: PathBuf) -> Self {
+        let mut state = Self {
+            directory: PathBuf::new(),
+            entries: Vec::new(),
+            path_text: String::new(),
+            error: None,
+            events: Vec::new(),
+        };
+        state.read_directory(directory);
+        state
+    }
+
+    // 읽지 못하면 원래 디렉토리에 머무른다
+    fn read_directory(&mut self, directory: PathBuf) {
+        let read_dir = match std::fs::read_dir(&directory) {
+            Ok(read_dir) => read_dir,
+            Err(e) => {
+                self.error = Some(format!("{}: {}", directory.display(), e));
+                return;
+            }
+        };
+
+        let mut entries: Vec<DirectoryEntry> = read_dir
+            .filter_map(|entry| entry.ok())
+            .map(|entry| DirectoryEntry {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                is_dir: entry.path().is_dir(),
+                path: entry.path(),
+            })
+            .filter(|entry| !entry.name.starts_with('.'))
+            .filter(|entry| entry.is_dir || is_gltf_path(&entry.path))
+            .collect();
+        // 디렉토리 먼저, 그 다음 이름 순
+        entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+
+        self.directory = directory;
+        self.entries = entries;
+        self.error = None;
+    }
+}
+
+impl<C: ViewContext<(), FileDialogCommand>> ViewState<(), C> for FileDialogViewState {
+    type Command = FileDialogCommand;
+
+    fn interact(&mut self, ui: &mut egui::Ui, _ctx: &C) {
+        egui::Window::new("Open glTF")
+            .collapsible(false)
+            .default_width(400.0)
+            .show(ui.ctx(), |ui| {
+                ui.horizontal(|ui| {
+                    let parent = self.directory.parent();
+                    if ui
+                        .add_enabled(parent.is_some(), egui::Button::new("Up"))
+                        .clicked()
+                    {
+                        self.events
+                            .push(Event::DirectoryEntered(parent.unwrap().to_path_buf()));
+                    }
+                    ui.label(self.directory.display().to_string());
+                });
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        for entry in &self.entries {
+                            if entry.is_dir {
+                                let label = format!("{}/", entry.name);
+                                if ui.selectable_label(false, label).clicked() {
+                                    self.events
+                                        .push(Event::DirectoryEntered(entry.path.clone()));
+                                }
+                            } else {
+                                let selected = Path::new(&self.path_text) == entry.path;
+                                let response = ui.selectable_label(selected, &entry.name);
+                                if response.double_clicked() {
+                                    self.events.push(Event::FileChosen(entry.path.clone()));
+                                } else if response.clicked() {
+                                    self.events.push(Event::FileSelected(entry.path.clone()));
+                                }
+                            }
+                        }
+                    });
+                ui.separator();
+
+                let text_edit = ui.text_edit_singleline(&mut self.path_text);
+                if text_edit.lost_focus() && ui.input().key_pressed(egui::Key::Enter) {
+                    self.events.push(Event::PathSubmitted);
+                }
+                if let Some(error) = &self.error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+                ui.horizontal(|ui| {
+                    let open = egui::Button::new("Open");
+                    if ui.add_enabled(!self.path_text.is_empty(), open).clicked() {
+                        self.events.push(Event::PathSubmitted);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.events.push(Event::Cancelled);
+                    }
+                });
+            });
+    }
+
+    fn mutate(&mut self, ctx: &mut C) {
+        for e in std::mem::take(&mut self.events) {
+            self.handle_event(ctx, e);
+        }
+    }
+}
+
+impl FileDialogViewState {
+    fn handle_event<C: ViewContext<(), FileDialogCommand>>(&mut self, ctx: &mut C, event: Event) {
+        match event {
+            Event::DirectoryEntered(directory) => {
+                self.read_directory(directory);
+            }
+            Event::FileSelected(path) => {
+                self.path_text = path.display().to_string();
+            }
+            Event::FileChosen(path) => {
+                ctx.push_command(FileDialogCommand::Open(path));
+            }
+            Event::PathSubmitted => {
+                // 상대 경로는 지금 보고 있는 디렉토리 기준
+                let path = self.directory.join(&self.path_text);
+                if path.is_dir() {
+                    self.path_text.clear();
+                    self.read_directory(path);
+                } else {
+                    ctx.push_command(FileDialogCommand::Open(path));
+                }
+            }
+            Event::Cancelled => {
+                ctx.push_command(FileDialogCommand::Close);
+            }
+        }
+    }
+}
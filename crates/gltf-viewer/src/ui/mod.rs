@@ -1,3 +1,4 @@
+pub mod file_dialog;
 pub mod framework;
 pub mod root;
 pub mod todo_list;
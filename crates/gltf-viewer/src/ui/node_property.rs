@@ -1,29 +1,105 @@
-use crate::command::{EngineCommand, EngineModel, UpdateFloatCommand};
+use crate::command::{
+    EngineCommand, EngineModel, UpdateEulerRotationCommand, UpdateFloatCommand,
+    UpdateRotationCommand,
+};
 use crate::ui::framework::{ViewContext, ViewState};
 use eframe::egui;
 use eframe::egui::Ui;
+use gltf_engine::cgmath::{Deg, Euler, InnerSpace, Quaternion, Rad};
+use gltf_engine::model::{EulerOrder, ImportedGltf, Node};
 use uuid::Uuid;
 
 pub enum Event {
     PositionXChanged(f32),
     PositionYChanged(f32),
     PositionZChanged(f32),
+    RotationModeChanged(RotationMode),
+    EulerOrderChanged(EulerOrder),
+    // x, y, z 축 회전각 (degree)
+    EulerChanged([f32; 3]),
+    QuaternionChanged(Quaternion<f32>),
     ScaleXChanged(f32),
     ScaleYChanged(f32),
     ScaleZChanged(f32),
+    // DragValue 를 끄는 동안 바뀐 값들은 undo 한 단계로 묶는다
+    DragStarted,
+    DragReleased,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RotationMode {
+    Euler,
+    Quaternion,
+}
+
+// quaternion 에서 Euler 각을 매 frame 다시 계산하면 ±180° 를 넘어갈 때나 gimbal lock 근처에서 값이 튄다
+// 그래서 마지막으로 보여준 각을 기억해 두고, rotation 이 바깥에서 (다른 command 로) 바뀌었을 때만 다시 계산한다
+struct EulerCache {
+    rotation: Quaternion<f32>,
+    order: EulerOrder,
+    degrees: [f32; 3],
 }
 
 pub trait NodePropertyViewContext<'a>: ViewContext<EngineModel<'a>, EngineCommand> {
     fn node_id(&self) -> Uuid;
+    fn begin_undo_group(&mut self);
+    fn end_undo_group(&mut self);
 }
 
 pub struct NodePropertyViewState {
     events: Vec<Event>,
+    rotation_mode: RotationMode,
+    euler_order: EulerOrder,
+    euler_cache: Option<EulerCache>,
 }
 
 impl NodePropertyViewState {
     pub fn new() -> Self {
-        Self { events: Vec::new() }
+        Self {
+            events: Vec::new(),
+            rotation_mode: RotationMode::Euler,
+            euler_order: EulerOrder::default(),
+            euler_cache: None,
+        }
+    }
+
+    // 다른 node 를 선택해도 rotation 편집 방식은 유지한다
+    pub fn for_another_node(&self) -> Self {
+        Self {
+            rotation_mode: self.rotation_mode,
+            euler_order: self.euler_order,
+            ..Self::new()
+        }
+    }
+
+    fn track_drag(&mut self, response: &egui::Response) {
+        if response.drag_started() {
+            self.events.push(Event::DragStarted);
+        }
+        if response.drag_released() {
+            self.events.push(Event::DragReleased);
+        }
+    }
+
+    fn euler_degrees(&mut self, rotation: Quaternion<f32>) -> [f32; 3] {
+        let order = self.euler_order;
+        match &self.euler_cache {
+            Some(cache)
+                if cache.order == order && (cache.rotation - rotation).magnitude2() < 1e-12 =>
+            {
+                cache.degrees
+            }
+            _ => {
+                let angles = order.to_euler(rotation);
+                let degrees = [angles.x, angles.y, angles.z].map(|angle| Deg::from(angle).0);
+                self.euler_cache = Some(EulerCache {
+                    rotation,
+                    order,
+                    degrees,
+                });
+                degrees
+            }
+        }
     }
 }
 
@@ -33,49 +109,125 @@ impl<'a, C: NodePropertyViewContext<'a>> ViewState<EngineModel<'a>, C> for NodeP
     fn interact(&mut self, ui: &mut Ui, ctx: &C) {
         use egui::widgets::DragValue;
 
-        let node = ctx
-            .model()
-            .engine
-            .model_root()
-            .nodes
-            .get(&ctx.node_id())
-            .unwrap();
-        ui.label(format!("Node {}", node.abbreviated_id()));
+        let Some(model_root) = ctx.model().engine.model_root() else {
+            return;
+        };
+        // 다른 glTF 를 불러왔으면 선택된 node 가 없을 수 있다
+        let Some(node) = model_root.nodes.get(&ctx.node_id()) else {
+            return;
+        };
+        ui.heading(node.label());
+        ui.label(format!(
+            "Node {} ({})",
+            node.gltf_index(),
+            node.abbreviated_id()
+        ));
         ui.label(format!("Children: {}", node.children.len()));
+        extras_label(ui, &node.extras);
         ui.separator();
         ui.label("Position");
         ui.horizontal(|ui| {
             let mut x = node.transform.position.x;
             let mut y = node.transform.position.y;
             let mut z = node.transform.position.z;
-            if ui.add(DragValue::new(&mut x).speed(0.01)).changed() {
+            let response = ui.add(DragValue::new(&mut x).speed(0.01));
+            self.track_drag(&response);
+            if response.changed() {
                 self.events.push(Event::PositionXChanged(x));
             }
-            if ui.add(DragValue::new(&mut y).speed(0.01)).changed() {
+            let response = ui.add(DragValue::new(&mut y).speed(0.01));
+            self.track_drag(&response);
+            if response.changed() {
                 self.events.push(Event::PositionYChanged(y));
             }
-            if ui.add(DragValue::new(&mut z).speed(0.01)).changed() {
+            let response = ui.add(DragValue::new(&mut z).speed(0.01));
+            self.track_drag(&response);
+            if response.changed() {
                 self.events.push(Event::PositionZChanged(z));
             }
         });
         ui.separator();
-        ui.label("Rotation (TODO)");
+        ui.horizontal(|ui| {
+            ui.label("Rotation");
+            for (mode, label) in [
+                (RotationMode::Euler, "Euler"),
+                (RotationMode::Quaternion, "Quaternion"),
+            ] {
+                if ui
+                    .selectable_label(self.rotation_mode == mode, label)
+                    .clicked()
+                {
+                    self.events.push(Event::RotationModeChanged(mode));
+                }
+            }
+        });
+        match self.rotation_mode {
+            RotationMode::Euler => {
+                egui::ComboBox::from_label("Order")
+                    .selected_text(self.euler_order.label())
+                    .show_ui(ui, |ui| {
+                        for order in EulerOrder::ALL {
+                            let selected = order == self.euler_order;
+                            if ui.selectable_label(selected, order.label()).clicked() && !selected {
+                                self.events.push(Event::EulerOrderChanged(order));
+                            }
+                        }
+                    });
+                let mut degrees = self.euler_degrees(node.transform.rotation);
+                ui.horizontal(|ui| {
+                    let mut changed = false;
+                    for degree in &mut degrees {
+                        let response = ui.add(DragValue::new(degree).speed(0.5).suffix("°"));
+                        self.track_drag(&response);
+                        changed |= response.changed();
+                    }
+                    if changed {
+                        self.events.push(Event::EulerChanged(degrees));
+                    }
+                });
+            }
+            RotationMode::Quaternion => {
+                let rotation = node.transform.rotation;
+                let mut xyzw = [rotation.v.x, rotation.v.y, rotation.v.z, rotation.s];
+                ui.horizontal(|ui| {
+                    let mut changed = false;
+                    for (value, prefix) in xyzw.iter_mut().zip(["x: ", "y: ", "z: ", "w: "]) {
+                        let response = ui.add(DragValue::new(value).speed(0.01).prefix(prefix));
+                        self.track_drag(&response);
+                        changed |= response.changed();
+                    }
+                    if changed {
+                        let [x, y, z, w] = xyzw;
+                        self.events
+                            .push(Event::QuaternionChanged(Quaternion::new(w, x, y, z)));
+                    }
+                });
+            }
+        }
         ui.separator();
         ui.label("Scale");
         ui.horizontal(|ui| {
             let mut x = node.transform.scale.x;
             let mut y = node.transform.scale.y;
             let mut z = node.transform.scale.z;
-            if ui.add(DragValue::new(&mut x).speed(0.01)).changed() {
+            let response = ui.add(DragValue::new(&mut x).speed(0.01));
+            self.track_drag(&response);
+            if response.changed() {
                 self.events.push(Event::ScaleXChanged(x));
-            };
-            if ui.add(DragValue::new(&mut y).speed(0.01)).changed() {
+            }
+            let response = ui.add(DragValue::new(&mut y).speed(0.01));
+            self.track_drag(&response);
+            if response.changed() {
                 self.events.push(Event::ScaleYChanged(y));
-            };
-            if ui.add(DragValue::new(&mut z).speed(0.01)).changed() {
+            }
+            let response = ui.add(DragValue::new(&mut z).speed(0.01));
+            self.track_drag(&response);
+            if response.changed() {
                 self.events.push(Event::ScaleZChanged(z));
             }
         });
+        ui.separator();
+        mesh_info(ui, model_root, node);
     }
 
     fn mutate(&mut self, ctx: &mut C) {
@@ -107,6 +259,36 @@ impl NodePropertyViewState {
                     value,
                 }))
             }
+            Event::RotationModeChanged(mode) => {
+                self.rotation_mode = mode;
+            }
+            Event::EulerOrderChanged(order) => {
+                self.euler_order = order;
+            }
+            Event::EulerChanged(degrees) => {
+                let [x, y, z] = degrees.map(|degree| Rad::from(Deg(degree)));
+                let angles = Euler::new(x, y, z);
+                let order = self.euler_order;
+                // 입력한 각을 그대로 보여주도록, 이 command 가 만들 rotation 으로 cache 를 갱신한다
+                self.euler_cache = Some(EulerCache {
+                    rotation: order.to_quaternion(angles),
+                    order,
+                    degrees,
+                });
+                ctx.push_command(EngineCommand::UpdateRotationEuler(
+                    UpdateEulerRotationCommand {
+                        node_id,
+                        angles,
+                        order,
+                    },
+                ))
+            }
+            Event::QuaternionChanged(value) => {
+                ctx.push_command(EngineCommand::UpdateRotation(UpdateRotationCommand {
+                    node_id,
+                    value,
+                }))
+            }
             Event::ScaleXChanged(value) => {
                 ctx.push_command(EngineCommand::UpdateScaleX(UpdateFloatCommand {
                     node_id,
@@ -125,6 +307,75 @@ impl NodePropertyViewState {
                     value,
                 }))
             }
+            Event::DragStarted => {
+                ctx.begin_undo_group();
+            }
+            Event::DragReleased => {
+                ctx.end_undo_group();
+            }
         }
     }
 }
+
+fn extras_label(ui: &mut Ui, extras: &Option<String>) {
+    if let Some(extras) = extras {
+        ui.label(format!("Extras: {}", extras));
+    }
+}
+
+// 편집은 안 되고 보여주기만 한다
+fn mesh_info(ui: &mut Ui, model_root: &ImportedGltf, node: &Node) {
+    let Some(mesh) = node.mesh_id.map(|mesh_id| &model_root.meshes[&mesh_id]) else {
+        ui.label("Mesh: None");
+        return;
+    };
+    ui.label(format!("Mesh: {}", mesh.label()));
+    extras_label(ui, &mesh.extras);
+
+    for (index, primitive) in mesh.primitives.iter().enumerate() {
+        let header = egui::CollapsingHeader::new(format!("Primitive {}", index))
+            .id_source((mesh.id, index))
+            .default_open(true);
+        header.show(ui, |ui| {
+            // import 할 때 건너뛴 primitive 도 glTF index 를 맞추려고 자리는 남겨 둔다
+            let Some(primitive) = primitive else {
+                ui.label("Skipped (no POSITION)");
+                return;
+            };
+            ui.label(format!("Topology: {:?}", primitive.topology));
+            ui.label(format!("Vertices: {}", primitive.positions.len()));
+            match &primitive.indices {
+                Some(indices) => ui.label(format!("Indices: {}", indices.len())),
+                None => ui.label("Indices: None"),
+            };
+            let attributes = [
+                ("NORMAL", primitive.normals.is_some()),
+                ("TANGENT", primitive.tangents.is_some()),
+                ("TEXCOORD_0", primitive.tex_coords.is_some()),
+                ("TEXCOORD_1", primitive.tex_coords_1.is_some()),
+                ("COLOR_0", primitive.colors.is_some()),
+            ];
+            let attributes: Vec<&str> = std::iter::once("POSITION")
+                .chain(
+                    attributes
+                        .iter()
+                        .filter(|(_, exists)| *exists)
+                        .map(|(name, _)| *name),
+                )
+                .collect();
+            ui.label(format!("Attributes: {}", attributes.join(", ")));
+
+            let Some(material) = primitive
+                .material_id
+                .map(|material_id| &model_root.materials[&material_id])
+            else {
+                ui.label("Material: Default");
+                return;
+            };
+            ui.label(format!("Material: {}", material.label()));
+            ui.label(format!("Alpha mode: {:?}", material.alpha_mode));
+            ui.label(format!("Double sided: {}", material.double_sided));
+            extras_label(ui, &material.extras);
+        });
+    }
+}
@@ -1,12 +1,16 @@
 use crate::command::{EngineCommand, EngineModel, TodoListCommand};
 use crate::model::TodoListModel;
+use crate::ui::file_dialog::{is_gltf_path, FileDialogCommand, FileDialogViewState};
 use crate::ui::framework::*;
 use crate::ui::node_property::{NodePropertyViewContext, NodePropertyViewState};
 use crate::ui::todo_list::{TodoListContext, TodoListViewState};
 use crate::undo_manager::UndoManager;
 use crate::PaintResource;
 use eframe::egui;
-use gltf_engine::{AbstractKey, InputEvent};
+use gltf_engine::model::Node;
+use gltf_engine::{AbstractKey, InputEvent, LoadProgress};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -19,7 +23,8 @@ pub enum WorkspaceKind {
 pub struct RootViewState {
     workspace: WorkspaceKind,
     node_selection: NodeSelection,
-    undo_manager: UndoManager,
+    file_dialog: Option<FileDialogViewState>,
+    undo_manager: UndoManager<TodoListCommand>,
     todo_list: TodoListModel,
     events: Vec<RootViewEvent>,
     engine_commands: Vec<EngineCommand>,
@@ -30,17 +35,53 @@ impl RootViewState {
         Self {
             workspace: WorkspaceKind::Layout,
             node_selection: NodeSelection::None,
+            file_dialog: None,
             undo_manager: UndoManager::new(),
             todo_list: TodoListModel::default(),
             events: Vec::new(),
             engine_commands: Vec::new(),
         }
     }
+
+    // 같은 파일을 다시 불러온 뒤, 이전 node id 를 새 node id 로 바꾼다. 새 model 에 없는 node 는 선택 해제한다
+    pub fn nodes_reloaded(&mut self, node_ids: &HashMap<Uuid, Uuid>) {
+        if let NodeSelection::SingleSelection { id, .. } = &mut self.node_selection {
+            match node_ids.get(id) {
+                Some(new_id) => *id = *new_id,
+                None => self.node_selection = NodeSelection::None,
+            }
+        }
+    }
+
+    // 다른 파일을 불러온 뒤. 다른 glTF 의 node 는 id 가 다르므로 선택을 해제한다
+    pub fn nodes_replaced(&mut self) {
+        self.node_selection = NodeSelection::None;
+    }
+
+    // Engine::set_selection 에 넘길 node 들
+    pub fn selected_node_ids(&self) -> Vec<Uuid> {
+        match &self.node_selection {
+            NodeSelection::None => Vec::new(),
+            NodeSelection::SingleSelection { id, .. } => vec![*id],
+        }
+    }
 }
 
-pub trait RootViewContext: ViewContext<(), EngineCommand> {
+// undo, redo 는 engine command 에 대한 것이다
+pub trait RootViewContext: ViewContext<(), EngineCommand> + UndoableViewContext {
     fn engine_model(&self) -> &EngineModel;
     fn request_repaint(&mut self);
+    // 최근에 연 것부터
+    fn recent_files(&self) -> &[PathBuf];
+    fn request_open_file(&mut self, path: PathBuf);
+    // 불러오는 중인 파일. 불러오는 중이 아니면 None
+    fn loading_path(&self) -> Option<&Path>;
+    // 아직 worker 로부터 진행 상황을 받지 못했으면 None
+    fn loading_progress(&self) -> Option<LoadProgress>;
+    fn request_cancel_loading(&mut self);
+    // 이 frame 의 command 를 처리하기 전에 undo group 을 시작하거나, 처리한 뒤에 끝낸다
+    fn begin_undo_group(&mut self);
+    fn end_undo_group(&mut self);
 }
 
 pub enum RootViewEvent {
@@ -48,6 +89,16 @@ pub enum RootViewEvent {
     ChangeWorkspace(WorkspaceKind),
     ExitRequested,
     SingleNodeSelected(Uuid),
+    NodeSelectionCleared,
+    SceneSelected(Uuid),
+    FileDialogOpened,
+    FileDialogClosed,
+    FileOpened(PathBuf),
+    LoadingCancelled,
+    UndoRequested,
+    RedoRequested,
+    UndoGroupBegan,
+    UndoGroupEnded,
 }
 
 impl<C: RootViewContext> ViewState<(), C> for RootViewState {
@@ -66,6 +117,17 @@ impl<C: RootViewContext> ViewState<(), C> for RootViewState {
             self.events.push(RootViewEvent::ExitRequested);
         };
 
+        // text 를 입력하는 중에는 TextEdit 이 직접 undo 한다. Todo List 는 따로 undo 버튼이 있다
+        if matches!(self.workspace, WorkspaceKind::Layout) && !ui.ctx().wants_keyboard_input() {
+            let mut input = ui.ctx().input_mut();
+            let redo_modifiers = egui::Modifiers::COMMAND | egui::Modifiers::SHIFT;
+            if input.consume_key(redo_modifiers, egui::Key::Z) {
+                self.events.push(RootViewEvent::RedoRequested);
+            } else if input.consume_key(egui::Modifiers::COMMAND, egui::Key::Z) {
+                self.events.push(RootViewEvent::UndoRequested);
+            }
+        }
+
         for e in &ui.ctx().input().events {
             log::debug!("MyApp event: {:?}", e);
             let input_event = match e {
@@ -108,11 +170,27 @@ impl<C: RootViewContext> ViewState<(), C> for RootViewState {
             }
         }
 
+        // 여러 개를 떨어뜨리면 첫 번째 glTF 만 연다
+        let dropped_path = ui
+            .ctx()
+            .input()
+            .raw
+            .dropped_files
+            .iter()
+            .filter_map(|file| file.path.as_ref())
+            .find(|path| is_gltf_path(path))
+            .cloned();
+        if let Some(path) = dropped_path {
+            self.events.push(RootViewEvent::FileOpened(path));
+        }
+
         self.top_panel(ui, ctx);
         self.bottom_panel(ui, ctx);
         self.left_panel(ui, ctx);
         self.right_panel(ui, ctx);
         self.central_panel(ui, ctx);
+        self.file_dialog(ui);
+        Self::drop_hint(ui);
     }
 
     fn mutate(&mut self, ctx: &mut C) {
@@ -126,7 +204,7 @@ impl<C: RootViewContext> ViewState<(), C> for RootViewState {
 }
 
 impl RootViewState {
-    fn top_panel<C: RootViewContext>(&mut self, ui: &mut egui::Ui, _ctx: &C) {
+    fn top_panel<C: RootViewContext>(&mut self, ui: &mut egui::Ui, ctx: &C) {
         let mut is_layout = false;
         let mut is_todo_list = false;
         let mut is_hello_world = false;
@@ -137,6 +215,8 @@ impl RootViewState {
         }
         egui::TopBottomPanel::top("my_panel").show(ui.ctx(), |ui| {
             ui.horizontal(|ui| {
+                self.file_menu(ui, ctx);
+                ui.separator();
                 if ui.selectable_label(is_layout, "Layout").clicked() && !is_layout {
                     self.events
                         .push(RootViewEvent::ChangeWorkspace(WorkspaceKind::Layout));
@@ -155,21 +235,107 @@ impl RootViewState {
         });
     }
 
-    fn bottom_panel<C: RootViewContext>(&mut self, ui: &mut egui::Ui, _ctx: &C) {
+    fn file_menu<C: RootViewContext>(&mut self, ui: &mut egui::Ui, ctx: &C) {
+        ui.menu_button("File", |ui| {
+            if ui.button("Open...").clicked() {
+                self.events.push(RootViewEvent::FileDialogOpened);
+                ui.close_menu();
+            }
+            ui.menu_button("Open Recent", |ui| {
+                if ctx.recent_files().is_empty() {
+                    ui.label("No recent files");
+                }
+                for path in ctx.recent_files() {
+                    if ui.button(path.display().to_string()).clicked() {
+                        self.events.push(RootViewEvent::FileOpened(path.clone()));
+                        ui.close_menu();
+                    }
+                }
+            });
+            ui.separator();
+            if ui.button("Quit").clicked() {
+                self.events.push(RootViewEvent::ExitRequested);
+                ui.close_menu();
+            }
+        });
+    }
+
+    fn file_dialog(&mut self, ui: &mut egui::Ui) {
+        let Some(file_dialog) = &mut self.file_dialog else {
+            return;
+        };
+        let mut context = FileDialogViewContextImpl {
+            commands: Vec::new(),
+        };
+        file_dialog.update(ui, &mut context);
+        for command in context.commands {
+            self.events.push(match command {
+                FileDialogCommand::Open(path) => RootViewEvent::FileOpened(path),
+                FileDialogCommand::Close => RootViewEvent::FileDialogClosed,
+            });
+        }
+    }
+
+    // 파일을 끌고 창 위에 올라와 있는 동안 보여준다
+    fn drop_hint(ui: &mut egui::Ui) {
+        if ui.ctx().input().raw.hovered_files.is_empty() {
+            return;
+        }
+        let painter = ui.ctx().layer_painter(egui::LayerId::new(
+            egui::Order::Foreground,
+            egui::Id::new("drop_hint"),
+        ));
+        let screen_rect = ui.ctx().input().screen_rect();
+        painter.rect_filled(screen_rect, 0.0, egui::Color32::from_black_alpha(160));
+        painter.text(
+            screen_rect.center(),
+            egui::Align2::CENTER_CENTER,
+            "Drop .gltf or .glb to open",
+            egui::FontId::proportional(24.0),
+            egui::Color32::WHITE,
+        );
+    }
+
+    fn bottom_panel<C: RootViewContext>(&mut self, ui: &mut egui::Ui, ctx: &C) {
         egui::TopBottomPanel::bottom("my_bottom_panel").show(ui.ctx(), |ui| {
-            ui.label("Hello World!");
+            let Some(path) = ctx.loading_path() else {
+                ui.label("Hello World!");
+                return;
+            };
+            ui.horizontal(|ui| {
+                let name = path.file_name().unwrap_or(path.as_os_str());
+                ui.label(format!("Loading {}", name.to_string_lossy()));
+                let progress_bar = match ctx.loading_progress() {
+                    Some(progress) => egui::ProgressBar::new(progress.fraction()).text(format!(
+                        "{} {}/{}",
+                        progress.stage.label(),
+                        progress.completed,
+                        progress.total
+                    )),
+                    None => egui::ProgressBar::new(0.0),
+                };
+                ui.add(progress_bar.desired_width(200.0));
+                if ui.button("Cancel").clicked() {
+                    self.events.push(RootViewEvent::LoadingCancelled);
+                }
+            });
         });
     }
 
     fn left_panel<C: RootViewContext>(&mut self, ui: &mut egui::Ui, ctx: &C) {
         egui::SidePanel::left("my_left_panel").show(ui.ctx(), |ui| {
             ui.heading("Node Tree");
+            self.scene_picker(ui, ctx);
             ui.separator();
             egui::ScrollArea::vertical()
                 .auto_shrink([false; 2])
                 .show(ui, |ui| {
-                    let model_root = ctx.engine_model().engine().model_root();
-                    let scene = &model_root.default_scene();
+                    let Some(model_root) = ctx.engine_model().engine().model_root() else {
+                        return;
+                    };
+                    let Some(scene) = model_root.active_scene() else {
+                        return;
+                    };
                     for &node_id in scene.nodes.iter() {
                         self.rec_node(ui, ctx, node_id);
                     }
@@ -177,16 +343,41 @@ impl RootViewState {
         });
     }
 
+    fn scene_picker<C: RootViewContext>(&mut self, ui: &mut egui::Ui, ctx: &C) {
+        let Some(model_root) = ctx.engine_model().engine().model_root() else {
+            return;
+        };
+        let Some(active_scene) = model_root.active_scene() else {
+            return;
+        };
+        egui::ComboBox::from_label("Scene")
+            .selected_text(active_scene.label())
+            .show_ui(ui, |ui| {
+                for scene in model_root.scenes_in_order() {
+                    let selected = scene.id == active_scene.id;
+                    if ui.selectable_label(selected, scene.label()).clicked() && !selected {
+                        self.events.push(RootViewEvent::SceneSelected(scene.id));
+                    }
+                }
+            });
+        if let Some(extras) = &active_scene.extras {
+            ui.label(format!("Extras: {}", extras));
+        }
+    }
+
     fn rec_node<C: RootViewContext>(&mut self, ui: &mut egui::Ui, ctx: &C, node_id: Uuid) {
-        let model_root = ctx.engine_model().engine().model_root();
+        let Some(model_root) = ctx.engine_model().engine().model_root() else {
+            return;
+        };
         let node = &model_root.nodes[&node_id];
 
-        let id_string = format!("Node {}", node.abbreviated_id());
-        let id = ui.make_persistent_id(&id_string);
+        // 이름은 겹칠 수 있으므로 펼침 상태는 id 로 구분한다
+        let label = node.label();
+        let id = ui.make_persistent_id(node.id);
         if node.children.is_empty() {
             ui.horizontal(|ui| {
                 let selected = self.node_selection.is_selected(node.id);
-                if ui.selectable_label(selected, &id_string).clicked() {
+                if node_label(ui, node, selected, &label).clicked() {
                     self.events.push(RootViewEvent::SingleNodeSelected(node.id));
                 };
             });
@@ -194,7 +385,7 @@ impl RootViewState {
             egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, true)
                 .show_header(ui, |ui| {
                     let selected = self.node_selection.is_selected(node.id);
-                    if ui.selectable_label(selected, &id_string).clicked() {
+                    if node_label(ui, node, selected, &label).clicked() {
                         self.events.push(RootViewEvent::SingleNodeSelected(node.id));
                     }
                 })
@@ -227,9 +418,17 @@ impl RootViewState {
                 node_id: *id,
                 model: ctx.engine_model(),
                 commands: Vec::new(),
+                begin_undo_group: false,
+                end_undo_group: false,
             };
             property_view.update(ui, &mut context);
             self.engine_commands.append(&mut context.commands);
+            if context.begin_undo_group {
+                self.events.push(RootViewEvent::UndoGroupBegan);
+            }
+            if context.end_undo_group {
+                self.events.push(RootViewEvent::UndoGroupEnded);
+            }
         }
     }
 
@@ -269,7 +468,7 @@ impl RootViewState {
         }
     }
 
-    fn central_panel<C: RootViewContext>(&mut self, ui: &mut egui::Ui, _ctx: &C) {
+    fn central_panel<C: RootViewContext>(&mut self, ui: &mut egui::Ui, ctx: &C) {
         let f = egui::Frame {
             inner_margin: egui::style::Margin {
                 left: 0.0,
@@ -286,6 +485,20 @@ impl RootViewState {
                 .show(ui, move |ui| {
                     egui::Frame::canvas(ui.style()).show(ui, |ui| {
                         let response = self.custom_painting(ui);
+                        // 빈 곳을 클릭하면 선택을 해제한다
+                        let clicked_pos = response
+                            .interact_pointer_pos()
+                            .filter(|_| response.clicked());
+                        if let Some(pos) = clicked_pos {
+                            let rect = response.rect;
+                            let x = (pos.x - rect.left()) / rect.width();
+                            let y = (pos.y - rect.top()) / rect.height();
+                            let event = match ctx.engine_model().engine().pick(x, y) {
+                                Some(hit) => RootViewEvent::SingleNodeSelected(hit.node_id),
+                                None => RootViewEvent::NodeSelectionCleared,
+                            };
+                            self.events.push(event);
+                        }
                         if response.drag_started()
                             && response.dragged_by(egui::PointerButton::Secondary)
                         {
@@ -319,7 +532,7 @@ impl RootViewState {
         // TODO: scale factor
         let (rect, response) = ui.allocate_at_least(
             egui::Vec2::new(available.width(), available.height()),
-            egui::Sense::drag(),
+            egui::Sense::click_and_drag(),
         );
 
         let cb = egui_wgpu::CallbackFn::new()
@@ -334,7 +547,7 @@ impl RootViewState {
                 if changed {
                     resource.update_bind_group(device);
                 }
-                resource.engine.update(queue);
+                resource.engine.update(device, queue);
                 // TODO: parallelize
                 let command_buffer = resource.engine.render(device).expect("Failed to render");
                 resource.engine.end_frame();
@@ -368,18 +581,82 @@ impl RootViewState {
                 ctx.request_exit();
             }
             RootViewEvent::SingleNodeSelected(node_id) => {
+                let property_view = match &self.node_selection {
+                    NodeSelection::SingleSelection { property_view, .. } => {
+                        property_view.for_another_node()
+                    }
+                    NodeSelection::None => NodePropertyViewState::new(),
+                };
                 self.node_selection = NodeSelection::SingleSelection {
                     id: node_id,
-                    property_view: NodePropertyViewState::new(),
+                    property_view,
                 };
+                ctx.push_command(EngineCommand::SetSelection(vec![node_id]));
+            }
+            RootViewEvent::NodeSelectionCleared => {
+                self.node_selection = NodeSelection::None;
+                ctx.push_command(EngineCommand::SetSelection(Vec::new()));
+            }
+            RootViewEvent::SceneSelected(scene_id) => {
+                // 보이지 않는 scene 의 node 를 계속 편집하지 않도록 선택을 해제한다
+                self.node_selection = NodeSelection::None;
+                ctx.push_command(EngineCommand::SetSelection(Vec::new()));
+                ctx.push_command(EngineCommand::SetActiveScene(scene_id));
+            }
+            RootViewEvent::FileDialogOpened => {
+                // 마지막으로 연 파일의 디렉토리에서 시작한다
+                let directory = ctx
+                    .recent_files()
+                    .first()
+                    .and_then(|path| path.parent())
+                    .map(|path| path.to_path_buf())
+                    .or_else(|| std::env::current_dir().ok())
+                    .unwrap_or_default();
+                self.file_dialog = Some(FileDialogViewState::new(directory));
+            }
+            RootViewEvent::FileDialogClosed => {
+                self.file_dialog = None;
+            }
+            RootViewEvent::FileOpened(path) => {
+                // 선택은 불러오기에 성공한 뒤에 해제한다 (nodes_replaced)
+                self.file_dialog = None;
+                ctx.request_open_file(path);
+            }
+            RootViewEvent::LoadingCancelled => {
+                ctx.request_cancel_loading();
+            }
+            RootViewEvent::UndoRequested => {
+                if ctx.can_undo() {
+                    ctx.request_undo();
+                }
+            }
+            RootViewEvent::RedoRequested => {
+                if ctx.can_redo() {
+                    ctx.request_redo();
+                }
+            }
+            RootViewEvent::UndoGroupBegan => {
+                ctx.begin_undo_group();
+            }
+            RootViewEvent::UndoGroupEnded => {
+                ctx.end_undo_group();
             }
         }
     }
 }
 
+// extras 가 있으면 마우스를 올렸을 때 보여준다
+fn node_label(ui: &mut egui::Ui, node: &Node, selected: bool, label: &str) -> egui::Response {
+    let response = ui.selectable_label(selected, label);
+    match &node.extras {
+        Some(extras) => response.on_hover_text(extras),
+        None => response,
+    }
+}
+
 pub struct TodoListContextImpl<'a> {
     model: &'a TodoListModel,
-    undo_manager: &'a UndoManager,
+    undo_manager: &'a UndoManager<TodoListCommand>,
     model_commands: &'a mut Vec<TodoListCommand>,
     undo_requested: bool,
     redo_requested: bool,
@@ -389,7 +666,7 @@ pub struct TodoListContextImpl<'a> {
 impl<'a> TodoListContextImpl<'a> {
     pub fn new(
         model: &'a TodoListModel,
-        undo_manager: &'a UndoManager,
+        undo_manager: &'a UndoManager<TodoListCommand>,
         model_commands: &'a mut Vec<TodoListCommand>,
     ) -> Self {
         Self {
@@ -453,6 +730,8 @@ struct NodePropertyViewContextImpl<'a> {
     node_id: Uuid,
     model: &'a EngineModel<'a>,
     commands: Vec<EngineCommand>,
+    begin_undo_group: bool,
+    end_undo_group: bool,
 }
 
 impl<'a> ViewContext<EngineModel<'a>, EngineCommand> for NodePropertyViewContextImpl<'a> {
@@ -477,6 +756,34 @@ impl<'a> NodePropertyViewContext<'a> for NodePropertyViewContextImpl<'a> {
     fn node_id(&self) -> Uuid {
         self.node_id
     }
+
+    fn begin_undo_group(&mut self) {
+        self.begin_undo_group = true;
+    }
+
+    fn end_undo_group(&mut self) {
+        self.end_undo_group = true;
+    }
+}
+
+struct FileDialogViewContextImpl {
+    commands: Vec<FileDialogCommand>,
+}
+
+impl ViewContext<(), FileDialogCommand> for FileDialogViewContextImpl {
+    fn model(&self) -> &() {
+        &()
+    }
+
+    fn push_command(&mut self, command: FileDialogCommand) {
+        self.commands.push(command);
+    }
+
+    fn exit_requested(&self) -> bool {
+        false
+    }
+
+    fn request_exit(&mut self) {}
 }
 
 enum NodeSelection {
@@ -1,39 +1,189 @@
-use crate::command::TodoListCommand;
-use crate::model::TodoListModel;
+// command 를 처리하고, 그 command 를 되돌리는 command 를 반환한다
+// 되돌릴 것이 없는 command (카메라 조작 등) 이거나 대상이 사라졌으면 None
+pub trait Undoable<C> {
+    fn apply(&mut self, command: C) -> Option<C>;
+}
 
-pub struct UndoManager {
-    undo_stack: Vec<TodoListCommand>,
-    redo_stack: Vec<TodoListCommand>,
+// undo 한 단계는 command 여러 개일 수 있다. 되돌릴 때는 역순으로 처리한다
+pub struct UndoManager<C> {
+    undo_stack: Vec<Vec<C>>,
+    redo_stack: Vec<Vec<C>>,
+    // begin_group 과 end_group 사이에 push_undo 한 command 들
+    group: Option<Vec<C>>,
 }
 
-impl UndoManager {
+impl<C> UndoManager<C> {
     pub fn new() -> Self {
         Self {
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
+            group: None,
         }
     }
 
     pub fn can_undo(&self) -> bool {
-        !self.undo_stack.is_empty()
+        !self.undo_stack.is_empty() || matches!(&self.group, Some(group) if !group.is_empty())
     }
 
     pub fn can_redo(&self) -> bool {
         !self.redo_stack.is_empty()
     }
 
-    pub fn undo(&mut self, model: &mut TodoListModel) {
-        let Some(command) = self.undo_stack.pop() else { return; };
-        self.redo_stack.push(model.process_command(command));
+    pub fn undo(&mut self, model: &mut impl Undoable<C>) {
+        self.end_group();
+        let Some(commands) = self.undo_stack.pop() else { return; };
+        let inverses = Self::apply_all(model, commands);
+        if !inverses.is_empty() {
+            self.redo_stack.push(inverses);
+        }
+    }
+
+    pub fn redo(&mut self, model: &mut impl Undoable<C>) {
+        self.end_group();
+        let Some(commands) = self.redo_stack.pop() else { return; };
+        let inverses = Self::apply_all(model, commands);
+        if !inverses.is_empty() {
+            self.undo_stack.push(inverses);
+        }
+    }
+
+    fn apply_all(model: &mut impl Undoable<C>, commands: Vec<C>) -> Vec<C> {
+        commands
+            .into_iter()
+            .rev()
+            .filter_map(|command| model.apply(command))
+            .collect()
+    }
+
+    pub fn push_undo(&mut self, command: C) {
+        self.redo_stack.clear();
+        match &mut self.group {
+            Some(group) => group.push(command),
+            None => self.undo_stack.push(vec![command]),
+        }
+    }
+
+    // slider 를 끄는 동안처럼 여러 frame 에 걸친 조작을 undo 한 단계로 묶는다
+    pub fn begin_group(&mut self) {
+        self.end_group();
+        self.group = Some(Vec::new());
     }
 
-    pub fn redo(&mut self, model: &mut TodoListModel) {
-        let Some(command) = self.redo_stack.pop() else { return; };
-        self.undo_stack.push(model.process_command(command));
+    pub fn end_group(&mut self) {
+        if let Some(group) = self.group.take() {
+            if !group.is_empty() {
+                self.undo_stack.push(group);
+            }
+        }
     }
 
-    pub fn push_undo(&mut self, command: TodoListCommand) {
+    // 다른 파일을 불러오는 등, 기록된 command 가 가리키는 대상이 사라졌을 때
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
         self.redo_stack.clear();
-        self.undo_stack.push(command);
+        self.group = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 값 하나를 바꾸는 command. 되돌리는 command 는 이전 값으로 바꾼다
+    struct Value(i32);
+
+    impl Undoable<i32> for Value {
+        fn apply(&mut self, command: i32) -> Option<i32> {
+            Some(std::mem::replace(&mut self.0, command))
+        }
     }
-}
\ No newline at end of file
+
+    fn set(manager: &mut UndoManager<i32>, model: &mut Value, value: i32) {
+        let inverse = model.apply(value).unwrap();
+        manager.push_undo(inverse);
+    }
+
+    #[test]
+    fn undo_and_redo_in_reverse_order() {
+        let mut manager = UndoManager::new();
+        let mut model = Value(0);
+        set(&mut manager, &mut model, 1);
+        set(&mut manager, &mut model, 2);
+
+        manager.undo(&mut model);
+        assert_eq!(model.0, 1);
+        manager.undo(&mut model);
+        assert_eq!(model.0, 0);
+        assert!(!manager.can_undo());
+
+        manager.redo(&mut model);
+        assert_eq!(model.0, 1);
+        manager.redo(&mut model);
+        assert_eq!(model.0, 2);
+        assert!(!manager.can_redo());
+    }
+
+    #[test]
+    fn new_command_clears_redo() {
+        let mut manager = UndoManager::new();
+        let mut model = Value(0);
+        set(&mut manager, &mut model, 1);
+        manager.undo(&mut model);
+        assert!(manager.can_redo());
+
+        set(&mut manager, &mut model, 2);
+        assert!(!manager.can_redo());
+        manager.redo(&mut model);
+        assert_eq!(model.0, 2);
+    }
+
+    #[test]
+    fn group_is_one_undo_step() {
+        let mut manager = UndoManager::new();
+        let mut model = Value(0);
+        set(&mut manager, &mut model, 1);
+        manager.begin_group();
+        for value in 2..5 {
+            set(&mut manager, &mut model, value);
+        }
+        manager.end_group();
+
+        manager.undo(&mut model);
+        assert_eq!(model.0, 1);
+        manager.redo(&mut model);
+        assert_eq!(model.0, 4);
+    }
+
+    #[test]
+    fn empty_group_adds_no_undo_step() {
+        let mut manager = UndoManager::new();
+        let mut model = Value(0);
+        set(&mut manager, &mut model, 1);
+        manager.undo(&mut model);
+        manager.begin_group();
+        manager.end_group();
+
+        assert!(!manager.can_undo());
+        assert!(manager.can_redo());
+    }
+
+    #[test]
+    fn clear_drops_history_and_group() {
+        let mut manager = UndoManager::new();
+        let mut model = Value(0);
+        set(&mut manager, &mut model, 1);
+        set(&mut manager, &mut model, 2);
+        manager.undo(&mut model);
+        manager.begin_group();
+        set(&mut manager, &mut model, 3);
+
+        manager.clear();
+        assert!(!manager.can_undo());
+        assert!(!manager.can_redo());
+        // 열려 있던 group 도 버리므로 이후의 command 는 따로 undo 된다
+        set(&mut manager, &mut model, 4);
+        set(&mut manager, &mut model, 5);
+        manager.undo(&mut model);
+        assert_eq!(model.0, 4);
+    }
+}